@@ -0,0 +1,49 @@
+use std::hint::black_box;
+use std::time::Instant;
+
+use strum::IntoEnumIterator;
+
+use oxidizer::constants::SAMPLE_RATE;
+use oxidizer::oscillator::Oscillator;
+use oxidizer::wavetype::{WaveType, OscillatorQuality};
+
+// Renders a full unison stack for each wave type and quality so the cost per voice can be
+// compared, run with `cargo run --release --example oscillator_benchmark`
+const UNISON_VOICES: usize = 16;
+const SECONDS: usize = 5;
+
+fn main() {
+    let samples = SAMPLE_RATE as usize * SECONDS;
+
+    println!("{} voices, {}s of audio each", UNISON_VOICES, SECONDS);
+    println!();
+
+    for wave_type in WaveType::iter() {
+        for quality in OscillatorQuality::iter() {
+            let mut voices: Vec<Oscillator> = (0..UNISON_VOICES)
+                .map(|i| {
+                    let mut osc = Oscillator::new(220.0 + i as f32, wave_type, 0.0);
+                    osc.set_quality(quality);
+                    osc
+                })
+                .collect();
+
+            let start = Instant::now();
+
+            let mut sum = 0.0;
+            for _ in 0..samples {
+                for osc in voices.iter_mut() {
+                    sum += osc.get_sample(5.0, 0.01);
+                }
+            }
+            black_box(sum);
+
+            let elapsed = start.elapsed();
+            let ns_per_sample = elapsed.as_nanos() as f64 / (samples * UNISON_VOICES) as f64;
+            let wave_name: &'static str = wave_type.into();
+            let quality_name: &'static str = quality.into();
+
+            println!("{:<10} {:<16} {:>8.2}ns per voice sample", wave_name, quality_name, ns_per_sample);
+        }
+    }
+}
@@ -2,6 +2,8 @@
 use rodio::{OutputStream, Source, Sink};
 use std::f32::consts::PI;
 
+use oxidizer::tuning::Tuning;
+
 fn main() {
     let (_stream, stream_handle) = OutputStream::try_default().expect("Failed to create output stream");
     let sink = Sink::try_new(&stream_handle).unwrap();
@@ -16,18 +18,13 @@ fn main() {
 
 struct TestNoise {
     sample_index: f32,
-    note: f32,
+    note: i32,
+    tuning: Tuning,
 }
 
 impl TestNoise {
     fn new() -> TestNoise{
-        return TestNoise { sample_index: 0.0, note: 2.0 }
-    }
-
-    fn get_frequency(i: f32) -> f32{
-        let base_frequency = 220.0;
-        let twelfth_root_of_two = (2.0 as f32).powf(1.0 / 12.0);
-        return base_frequency * twelfth_root_of_two.powf(i as f32);
+        return TestNoise { sample_index: 0.0, note: 59, tuning: Tuning::default() }
     }
 }
 
@@ -35,7 +32,7 @@ impl Iterator for TestNoise {
     type Item = f32;
 
     fn next(&mut self) -> Option<f32> {
-        let w = Self::get_frequency(self.note) * 2.0 * PI * (self.sample_index / 44100.0);
+        let w = self.tuning.get_frequency(self.note) * 2.0 * PI * (self.sample_index / 44100.0);
         self.sample_index += 1.0;
         self.sample_index %= 44100.0;
 
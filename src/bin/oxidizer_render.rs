@@ -0,0 +1,150 @@
+use std::process;
+use std::sync::Arc;
+
+use oxidizer::midi_file::MidiFile;
+use oxidizer::preset::Preset;
+use oxidizer::scala::{Scale, KeyboardMapping};
+use oxidizer::synthesizer::{Synthesizer, SynthEvent, TimedSynthEvent};
+use oxidizer::wav::{self, WavFormat};
+use oxidizer::wavetables::WaveTable;
+
+// Extra time after the last release has finished so the file doesn't end abruptly
+const TAIL_PADDING: f32 = 0.25;
+
+struct RenderArgs {
+    input: String,
+    output: String,
+    preset: String,
+    format: WavFormat,
+    dither: bool,
+    scale: Option<String>,
+    keyboard_mapping: Option<String>,
+    wavetable: Option<String>,
+}
+
+fn print_usage() {
+    let preset_names: Vec<&str> = Preset::get_presets().iter().map(|preset| preset.name).collect();
+
+    eprintln!("Usage: oxidizer_render <input.mid> <output.wav> [options]");
+    eprintln!();
+    eprintln!("Options:");
+    eprintln!("  --preset <name>      One of: {}", preset_names.join(", "));
+    eprintln!("  --format <format>    16, 24 or 32f (default 16)");
+    eprintln!("  --no-dither          Disable TPDF dither for integer formats");
+    eprintln!("  --scl <file.scl>     Scala scale to tune to (default 12 tone equal temperament)");
+    eprintln!("  --kbm <file.kbm>     Scala keyboard mapping for the scale");
+    eprintln!("  --wavetable <file>   WAV wavetable to play on oscillator 1 instead of its wave form");
+}
+
+fn parse_args(args: &[String]) -> Result<RenderArgs, String> {
+    let mut positional: Vec<String> = Vec::new();
+    let mut preset = String::from("init");
+    let mut format = WavFormat::Int16;
+    let mut dither = true;
+    let mut scale = None;
+    let mut keyboard_mapping = None;
+    let mut wavetable = None;
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--preset" => {
+                preset = iter.next().ok_or("--preset requires a name")?.clone();
+            },
+            "--format" => {
+                format = match iter.next().map(|s| s.as_str()) {
+                    Some("16") => WavFormat::Int16,
+                    Some("24") => WavFormat::Int24,
+                    Some("32f") => WavFormat::Float32,
+                    other => return Err(format!("Unknown format {:?}, expected 16, 24 or 32f", other.unwrap_or(""))),
+                };
+            },
+            "--no-dither" => dither = false,
+            "--scl" => {
+                scale = Some(iter.next().ok_or("--scl requires a file")?.clone());
+            },
+            "--kbm" => {
+                keyboard_mapping = Some(iter.next().ok_or("--kbm requires a file")?.clone());
+            },
+            "--wavetable" => {
+                wavetable = Some(iter.next().ok_or("--wavetable requires a file")?.clone());
+            },
+            _ if arg.starts_with("--") => return Err(format!("Unknown option {}", arg)),
+            _ => positional.push(arg.clone()),
+        }
+    }
+
+    if positional.len() != 2 {
+        return Err(String::from("Expected an input MIDI file and an output WAV file"));
+    }
+
+    return Ok(RenderArgs {
+        input: positional[0].clone(),
+        output: positional[1].clone(),
+        preset,
+        format,
+        dither,
+        scale,
+        keyboard_mapping,
+        wavetable,
+    });
+}
+
+fn run(args: RenderArgs) -> Result<(), String> {
+    let preset = Preset::from_name(&args.preset).ok_or(format!("Unknown preset {}", args.preset))?;
+    let midi_file = MidiFile::from_file(&args.input).map_err(|e| format!("{}: {}", args.input, e))?;
+
+    let mut events: Vec<TimedSynthEvent> = preset
+        .to_synth_events()
+        .into_iter()
+        .map(|event| TimedSynthEvent::new(0.0, event))
+        .collect();
+
+    if let Some(path) = &args.scale {
+        let scale = Scale::from_file(path).map_err(|e| format!("{}: {}", path, e))?;
+        events.push(TimedSynthEvent::new(0.0, SynthEvent::ChangeScale(Some(scale))));
+    }
+
+    if let Some(path) = &args.keyboard_mapping {
+        let keyboard_mapping = KeyboardMapping::from_file(path).map_err(|e| format!("{}: {}", path, e))?;
+        events.push(TimedSynthEvent::new(0.0, SynthEvent::ChangeKeyboardMapping(Some(keyboard_mapping))));
+    }
+
+    if let Some(path) = &args.wavetable {
+        let wavetable = WaveTable::from_wav_file(path, None).map_err(|e| format!("{}: {}", path, e))?;
+
+        let mut osc_params = preset.oscillators[0].clone();
+        osc_params.wavetable = Some(Arc::new(wavetable));
+        events.push(TimedSynthEvent::new(0.0, SynthEvent::ChangeSoundGenOscParams(osc_params)));
+    }
+
+    events.extend(midi_file.to_synth_events());
+
+    let duration = midi_file.get_duration() + preset.get_longest_release() + TAIL_PADDING;
+
+    let mut synth = Synthesizer::new_offline();
+    let samples = synth.render(events, duration);
+
+    wav::write_wav(&args.output, &samples, args.format, args.dither).map_err(|e| format!("{}: {}", args.output, e))?;
+
+    println!("Rendered {:.2}s of {} with preset {} to {}", duration, args.input, preset.name, args.output);
+    return Ok(());
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    let render_args = match parse_args(&args) {
+        Ok(render_args) => render_args,
+        Err(error) => {
+            eprintln!("{}", error);
+            print_usage();
+            process::exit(1);
+        },
+    };
+
+    if let Err(error) = run(render_args) {
+        eprintln!("{}", error);
+        process::exit(1);
+    }
+}
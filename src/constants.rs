@@ -1,25 +1,295 @@
-use strum_macros::EnumIter;
+use std::sync::Arc;
+
+use strum_macros::{EnumIter, EnumString, EnumVariantNames, IntoStaticStr};
 use strum::{EnumCount, IntoEnumIterator};
 
-use crate::wavetype::WaveType;
+use crate::wavetables::WaveTable;
+use crate::tuning::A4_MIDI_NOTE;
+use crate::wavetype::{WaveType, OscillatorQuality, PhaseMode, OscModulation, FmAlgorithm, FilterMode, EnvelopeDestination, EnvelopeCurve};
 
 pub const SAMPLE_RATE: f32 = 44100.0;
-pub const NUM_CHANNELS: u16 = 1;
+pub const NUM_CHANNELS: u16 = 2;
 
 pub const MAX_NOTES: usize = 16;
 
-#[derive(EnumCount, EnumIter, Copy, Clone)]
+// Fraction of each cycle a pulse wave spends high. Widths are kept clear of 0.0 and 1.0
+// where the wave would go silent.
+pub const DEFAULT_PULSE_WIDTH: f32 = 0.2;
+pub const MIN_PULSE_WIDTH: f32 = 0.01;
+pub const MAX_PULSE_WIDTH: f32 = 0.99;
+
+// MIDI note of the lowest key on the computer keyboard and on screen piano (A3)
+pub const KEYBOARD_BASE_NOTE: i32 = 57;
+
+// Velocity used for notes played from the computer keyboard or on screen piano
+pub const DEFAULT_VELOCITY: f32 = 1.0;
+
+#[derive(Debug, PartialEq, EnumCount, EnumIter, IntoStaticStr, EnumString, EnumVariantNames, Copy, Clone)]
 pub enum OscNumber {
     Osc1,
     Osc2,
     Osc3,
 }
 
-#[derive(Default, Clone)]
+// Unison spread and start phase from 0.0 to 1.0, gain in dB, pan from -1.0 to 1.0, octave and semitone offsets and fine tune in cents
+pub enum OscillatorParam {
+    UnisonSpread,
+    StartPhase,
+    Gain,
+    Pan,
+    Octave,
+    Semitone,
+    FineTune,
+}
+
+#[derive(Clone)]
 pub struct LfoParams {
     pub enabled: bool,
     pub wave_type: WaveType,
-    pub frequency: f32
+    pub frequency: f32,
+    // Off leaves the LFO for pulse width modulation only
+    pub vibrato: bool,
+}
+
+impl Default for LfoParams {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            wave_type: WaveType::default(),
+            frequency: 0.0,
+            vibrato: true,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct FmParams {
+    pub algorithm: FmAlgorithm,
+    // Peak phase deviation in radians a modulator at full level causes
+    pub index: f32,
+    // Envelope of the index for each note, sustain is from 0.0 to 1.0 of the index
+    pub attack: f32,
+    pub decay: f32,
+    pub sustain: f32,
+    pub release: f32,
+}
+
+impl Default for FmParams {
+    fn default() -> Self {
+        Self {
+            algorithm: FmAlgorithm::default(),
+            index: 2.0,
+            attack: 0.0,
+            decay: 0.5,
+            sustain: 0.5,
+            release: 0.3,
+        }
+    }
+}
+
+// Square or sine an octave or two below the note, outside of the numbered oscillators
+#[derive(Clone)]
+pub struct SubOscParams {
+    pub enabled: bool,
+    pub wave_type: WaveType,
+    // -1 or -2
+    pub octave: i32,
+    // Level in dB
+    pub gain: f32,
+}
+
+impl Default for SubOscParams {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            wave_type: WaveType::Square,
+            octave: -1,
+            gain: -6.0,
+        }
+    }
+}
+
+pub enum FilterParam {
+    Cutoff,
+    Resonance,
+    Drive,
+    KeyTracking,
+    VelocityAmount,
+}
+
+// Filter on each voice. The cutoff moves in octaves from key tracking, velocity and any envelope sent to it
+#[derive(Clone)]
+pub struct FilterParams {
+    pub mode: FilterMode,
+    // Hz at A4 with no velocity or envelope
+    pub cutoff: f32,
+    // 0.0 to 1.0
+    pub resonance: f32,
+    // Ladder input gain in dB
+    pub drive: f32,
+    // 1.0 follows the keyboard exactly, 0.0 keeps the same cutoff on every note
+    pub key_tracking: f32,
+    // Octaves added at full velocity
+    pub velocity_amount: f32,
+}
+
+impl Default for FilterParams {
+    fn default() -> Self {
+        Self {
+            mode: FilterMode::default(),
+            cutoff: 2000.0,
+            resonance: 0.0,
+            drive: 0.0,
+            key_tracking: 0.0,
+            velocity_amount: 0.0,
+        }
+    }
+}
+
+impl FilterParams {
+    // velocity from 0.0 to 1.0 and envelope modulation in octaves
+    pub fn get_cutoff(&self, note: i32, velocity: f32, envelope_octaves: f32) -> f32 {
+        let octaves = self.key_tracking * (note - A4_MIDI_NOTE) as f32 / 12.0
+            + self.velocity_amount * velocity
+            + envelope_octaves;
+
+        return self.cutoff * octaves.exp2();
+    }
+}
+
+// Steepest bend of an exponential or logarithmic curve, at full tension
+const MAX_CURVE_STEEPNESS: f32 = 10.0;
+
+// Shape of one envelope stage, tension from 0.0 to 1.0 sets how far a curve bends away from linear
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct EnvelopeShape {
+    pub curve: EnvelopeCurve,
+    pub tension: f32,
+}
+
+impl Default for EnvelopeShape {
+    fn default() -> Self {
+        Self {
+            curve: EnvelopeCurve::default(),
+            tension: 0.5,
+        }
+    }
+}
+
+impl EnvelopeShape {
+    // Maps progress through the stage from 0.0 to 1.0 onto how far the level has moved from
+    // 0.0 to 1.0, so both ends stay where a linear stage would put them
+    pub fn apply(&self, progress: f32) -> f32 {
+        let progress = progress.clamp(0.0, 1.0);
+        let steepness = MAX_CURVE_STEEPNESS * self.tension.clamp(0.0, 1.0);
+
+        // Too gentle to tell apart from linear, and the formula divides by nearly zero
+        if self.curve == EnvelopeCurve::Linear || steepness < 0.001 {
+            return progress;
+        }
+
+        let exponential = |x: f32| (1.0 - (-steepness * x).exp()) / (1.0 - (-steepness).exp());
+
+        return match self.curve {
+            EnvelopeCurve::Exponential => exponential(progress),
+            EnvelopeCurve::Logarithmic => 1.0 - exponential(1.0 - progress),
+            EnvelopeCurve::Linear => progress,
+        };
+    }
+}
+
+#[derive(Debug, PartialEq, EnumCount, EnumIter, IntoStaticStr, EnumString, EnumVariantNames, Copy, Clone)]
+pub enum ModEnvelopeNumber {
+    Filter,
+    Mod,
+}
+
+// Envelope run on each voice and added to its destination. The amount at the envelope's peak
+// is in octaves of cutoff, semitones of pitch, pulse width or radians of FM index.
+#[derive(Clone)]
+pub struct ModEnvelopeParams {
+    pub num: ModEnvelopeNumber,
+    pub destination: EnvelopeDestination,
+    pub amount: f32,
+    // Sustain is from 0.0 to 1.0 of the amount
+    pub attack: f32,
+    pub decay: f32,
+    pub sustain: f32,
+    pub release: f32,
+}
+
+impl ModEnvelopeParams {
+    // The filter envelope starts on the cutoff and the mod envelope on pitch, both with no amount
+    pub fn create_default_array() -> [ModEnvelopeParams; ModEnvelopeNumber::COUNT] {
+        return ModEnvelopeNumber::iter()
+            .map(|num| ModEnvelopeParams {
+                num,
+                destination: match num {
+                    ModEnvelopeNumber::Filter => EnvelopeDestination::FilterCutoff,
+                    ModEnvelopeNumber::Mod => EnvelopeDestination::Pitch,
+                },
+                amount: 0.0,
+                attack: 0.0,
+                decay: 0.5,
+                sustain: 0.0,
+                release: 0.3,
+            })
+            .collect::<Vec<ModEnvelopeParams>>()
+            .try_into()
+            .unwrap_or_else(|v: Vec<ModEnvelopeParams>| panic!("Expected a Vec of length {} but it was {}", ModEnvelopeNumber::COUNT, v.len()));
+    }
+}
+
+#[derive(Debug, PartialEq, Copy, Clone, EnumIter, IntoStaticStr, EnumString, EnumVariantNames)]
+pub enum VelocityCurve {
+    Linear,
+    Soft,
+    Hard,
+    Fixed,
+}
+
+impl VelocityCurve {
+    // Maps a 0.0 to 1.0 velocity onto the curve. Soft gives more level to gentle
+    // playing, hard needs a firmer touch to get loud.
+    pub fn apply(&self, velocity: f32) -> f32 {
+        let velocity = velocity.clamp(0.0, 1.0);
+
+        return match self {
+            VelocityCurve::Linear => velocity,
+            VelocityCurve::Soft => velocity.sqrt(),
+            VelocityCurve::Hard => velocity * velocity,
+            VelocityCurve::Fixed => 1.0,
+        };
+    }
+}
+
+#[derive(Clone)]
+pub struct VelocityParams {
+    pub curve: VelocityCurve,
+    // How much velocity scales note level, 0.0 ignores velocity
+    pub amplitude_amount: f32,
+    // How much a hard hit shortens the attack, 1.0 makes a full velocity attack instant
+    pub attack_amount: f32,
+}
+
+impl Default for VelocityParams {
+    fn default() -> Self {
+        Self {
+            curve: VelocityCurve::Linear,
+            amplitude_amount: 1.0,
+            attack_amount: 0.0,
+        }
+    }
+}
+
+impl VelocityParams {
+    pub fn get_amplitude(&self, velocity: f32) -> f32 {
+        return 1.0 - self.amplitude_amount + self.amplitude_amount * self.curve.apply(velocity);
+    }
+
+    pub fn get_attack_scale(&self, velocity: f32) -> f32 {
+        return 1.0 - self.attack_amount * self.curve.apply(velocity);
+    }
 }
 
 #[derive(Clone)]
@@ -27,8 +297,30 @@ pub struct SoundGenOscParams {
     pub num: OscNumber,
     pub enabled: bool,
     pub wave_type: WaveType,
+    pub quality: OscillatorQuality,
+    pub pulse_width: f32,
+    // How far the LFO moves the pulse width either way
+    pub pwm_depth: f32,
     pub unisons: i32,
-    pub unison_detune_pct: f32
+    pub unison_detune_pct: f32,
+    // Stereo width of the unison voices from 0.0 all centred to 1.0 with the outer pair hard panned
+    pub unison_spread: f32,
+    pub phase_mode: PhaseMode,
+    // Phase from 0.0 to 1.0 of a cycle voices start at in the fixed phase mode
+    pub start_phase: f32,
+    // Oscillator this one is synced to or modulated by, ignored when it's itself or disabled
+    pub modulation: OscModulation,
+    pub modulator: OscNumber,
+    // Replaces the wave type when loaded, position morphs from its first frame to its last
+    pub wavetable: Option<Arc<WaveTable>>,
+    pub wavetable_position: f32,
+    // Level in dB
+    pub gain: f32,
+    // -1.0 hard left to 1.0 hard right
+    pub pan: f32,
+    pub octave: i32,
+    pub semitone: i32,
+    pub fine_tune_cents: f32,
 }
 
 impl SoundGenOscParams {
@@ -40,8 +332,23 @@ impl SoundGenOscParams {
                 num: osc_num,
                 enabled: i == 0,
                 wave_type: WaveType::default(),
+                quality: OscillatorQuality::default(),
+                pulse_width: DEFAULT_PULSE_WIDTH,
+                pwm_depth: 0.0,
                 unisons: 1,
-                unison_detune_pct: 0.2
+                unison_detune_pct: 0.2,
+                unison_spread: 0.5,
+                phase_mode: PhaseMode::default(),
+                start_phase: 0.0,
+                modulation: OscModulation::default(),
+                modulator: OscNumber::Osc1,
+                wavetable: None,
+                wavetable_position: 0.0,
+                gain: 0.0,
+                pan: 0.0,
+                octave: 0,
+                semitone: 0,
+                fine_tune_cents: 0.0,
             };
 
             sound_gen_oscillators.push(osc);
@@ -52,4 +359,9 @@ impl SoundGenOscParams {
                     .unwrap_or_else(|v: Vec<SoundGenOscParams>| panic!("Expected a Vec of length {} but it was {}", OscNumber::COUNT, v.len()));
 
     }
+
+    // Coarse and fine tuning together in semitones
+    pub fn get_pitch_offset(&self) -> f32 {
+        return (self.octave * 12 + self.semitone) as f32 + self.fine_tune_cents / 100.0;
+    }
 }
\ No newline at end of file
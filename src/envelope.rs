@@ -1,9 +1,22 @@
+use strum::EnumCount;
+
+use crate::constants::{SAMPLE_RATE, ModEnvelopeParams, EnvelopeShape};
+use crate::wavetype::{EnvelopeDestination, EnvelopeStage};
+
+// Levels of the amplitude envelope, the peak sets the synth's overall volume
+pub const DEFAULT_START_AMPLITUDE: f32 = 0.11;
+pub const DEFAULT_SUSTAIN_AMPLITUDE: f32 = 0.1;
+// Shortest stage, so a stage set to zero time doesn't divide by zero
+pub const MIN_STAGE_TIME: f32 = 1.0 / SAMPLE_RATE;
+
 pub struct EnvelopeADSR{
     attack_time: f32,
     decay_time: f32,
     release_time: f32,
     sustain_amplitude: f32,
     start_amplitude: f32,
+    // Curve of the attack, decay and release
+    shapes: [EnvelopeShape; EnvelopeStage::COUNT],
 }
 
 impl EnvelopeADSR{
@@ -12,27 +25,39 @@ impl EnvelopeADSR{
             attack_time: 0.1,
             decay_time: 1.0,
             release_time: 0.1,
-            sustain_amplitude: 0.1,
-            start_amplitude: 0.11,
+            sustain_amplitude: DEFAULT_SUSTAIN_AMPLITUDE,
+            start_amplitude: DEFAULT_START_AMPLITUDE,
+            shapes: [EnvelopeShape::default(); EnvelopeStage::COUNT],
         }
     }
 
-    pub fn get_amplitude(&self, time: f32, trigger_on_time: f32, trigger_off_time: f32, note_pressed: bool) -> f32 {
+    // Peaks at start_amplitude after the attack and decays to sustain_amplitude
+    pub fn with_levels(start_amplitude: f32, sustain_amplitude: f32) -> EnvelopeADSR {
+        return EnvelopeADSR{
+            start_amplitude,
+            sustain_amplitude,
+            ..Self::new()
+        };
+    }
+
+    // attack_scale shortens or lengthens the attack for a single note, e.g. from velocity
+    pub fn get_amplitude(&self, time: f32, trigger_on_time: f32, trigger_off_time: f32, note_pressed: bool, attack_scale: f32) -> f32 {
         let mut amp: f32;
+        let attack_time = (self.attack_time * attack_scale).max(MIN_STAGE_TIME);
 
         if note_pressed {
             let lifetime = time - trigger_on_time;
 
             // ADS
-            if lifetime <= self.attack_time {
+            if lifetime <= attack_time {
                 // Attack
-                amp = (lifetime / self.attack_time) * self.start_amplitude; 
+                amp = self.shaped(EnvelopeStage::Attack, lifetime / attack_time) * self.start_amplitude;
             }
-            else if lifetime > self.attack_time && lifetime <= self.decay_time {
+            else if lifetime > attack_time && lifetime <= attack_time + self.decay_time {
                 // Decay
-                amp = ((lifetime - self.attack_time) / self.decay_time) * (self.sustain_amplitude - self.start_amplitude) + self.start_amplitude;
+                amp = self.shaped(EnvelopeStage::Decay, (lifetime - attack_time) / self.decay_time) * (self.sustain_amplitude - self.start_amplitude) + self.start_amplitude;
             }
-            else { // lifetime > self.attack_time + self.decay_time
+            else { // lifetime > attack_time + self.decay_time
                 // Sustain
                 amp = self.sustain_amplitude;
             }
@@ -43,17 +68,17 @@ impl EnvelopeADSR{
 
             let lifetime = trigger_off_time - trigger_on_time;
             // Never reached full amplitude
-            if lifetime <= self.attack_time {
-                release_amplitude = (lifetime / self.attack_time) * self.start_amplitude; 
+            if lifetime <= attack_time {
+                release_amplitude = self.shaped(EnvelopeStage::Attack, lifetime / attack_time) * self.start_amplitude;
             }
-            else if lifetime > self.attack_time && lifetime <= self.decay_time {
-                release_amplitude = ((lifetime - self.attack_time) / self.decay_time) * (self.sustain_amplitude - self.start_amplitude) + self.start_amplitude;
+            else if lifetime > attack_time && lifetime <= attack_time + self.decay_time {
+                release_amplitude = self.shaped(EnvelopeStage::Decay, (lifetime - attack_time) / self.decay_time) * (self.sustain_amplitude - self.start_amplitude) + self.start_amplitude;
             }
-            else { // lifetime > self.attack_time + self.decay_time
+            else { // lifetime > attack_time + self.decay_time
                 release_amplitude = self.sustain_amplitude;
             }
 
-            amp = ((time - trigger_off_time) / self.release_time) * (0.0 - release_amplitude) + release_amplitude; 
+            amp = self.shaped(EnvelopeStage::Release, (time - trigger_off_time) / self.release_time) * (0.0 - release_amplitude) + release_amplitude;
         }
         
         if amp <= 0.0001 {
@@ -69,10 +94,68 @@ impl EnvelopeADSR{
     }
 
     pub fn set_decay_time(&mut self, decay: f32){
-        self.decay_time = decay;
+        self.decay_time = decay.max(MIN_STAGE_TIME);
     }
 
     pub fn set_release_time(&mut self, release: f32){
-        self.release_time = release;
+        self.release_time = release.max(MIN_STAGE_TIME);
+    }
+
+    // The sustain can't rise above the peak the decay falls from
+    pub fn set_sustain_amplitude(&mut self, sustain: f32){
+        self.sustain_amplitude = sustain.clamp(0.0, self.start_amplitude);
+    }
+
+    pub fn set_shape(&mut self, stage: EnvelopeStage, shape: EnvelopeShape){
+        self.shapes[stage as usize] = shape;
     }
-}
\ No newline at end of file
+
+    // How far through its levels a stage is after `progress` of its time
+    fn shaped(&self, stage: EnvelopeStage, progress: f32) -> f32 {
+        return self.shapes[stage as usize].apply(progress);
+    }
+}
+
+// A modulation envelope running on a single note, keeping its own level for the note to read
+pub struct VoiceEnvelope {
+    envelope: EnvelopeADSR,
+    destination: EnvelopeDestination,
+    amount: f32,
+    level: f32,
+}
+
+impl VoiceEnvelope {
+    pub fn new(params: &ModEnvelopeParams) -> VoiceEnvelope {
+        let mut voice_envelope = VoiceEnvelope {
+            envelope: EnvelopeADSR::new(),
+            destination: params.destination,
+            amount: params.amount,
+            level: 0.0,
+        };
+
+        voice_envelope.set_params(params);
+        return voice_envelope;
+    }
+
+    pub fn set_params(&mut self, params: &ModEnvelopeParams){
+        self.envelope = EnvelopeADSR::with_levels(1.0, params.sustain);
+        self.envelope.set_attack_time(params.attack);
+        self.envelope.set_decay_time(params.decay);
+        self.envelope.set_release_time(params.release);
+        self.destination = params.destination;
+        self.amount = params.amount;
+    }
+
+    pub fn update(&mut self, time: f32, trigger_on_time: f32, trigger_off_time: f32, note_pressed: bool){
+        self.level = self.envelope.get_amplitude(time, trigger_on_time, trigger_off_time, note_pressed, 1.0);
+    }
+
+    // The amount scaled by the current level, 0.0 for any other destination
+    pub fn get_modulation(&self, destination: EnvelopeDestination) -> f32 {
+        if destination != self.destination {
+            return 0.0;
+        }
+
+        return self.amount * self.level;
+    }
+}
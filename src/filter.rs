@@ -0,0 +1,144 @@
+use std::f32::consts::PI;
+
+use crate::constants::SAMPLE_RATE;
+use crate::wavetype::FilterMode;
+
+// Lowest and highest cutoffs, the top is kept under Nyquist where the filter's tan warping blows up
+pub const MIN_CUTOFF: f32 = 20.0;
+pub const MAX_CUTOFF: f32 = SAMPLE_RATE * 0.49;
+
+// Zero delay feedback state variable filter, Andrew Simper's trapezoidal integrator version.
+// It matches the analog 12dB/oct filter with the frequency axis warped to fit below Nyquist,
+// and stays stable however fast the cutoff moves. Each stereo channel keeps its own state.
+pub struct StateVariableFilter {
+    mode: FilterMode,
+    // Damping, 2.0 at no resonance down towards 0.0 where the filter rings forever
+    k: f32,
+    a1: f32,
+    a2: f32,
+    a3: f32,
+    // Integrator states for the left and right channels
+    ic1eq: [f32; 2],
+    ic2eq: [f32; 2],
+}
+
+impl StateVariableFilter {
+    pub fn new(mode: FilterMode) -> StateVariableFilter {
+        let mut filter = StateVariableFilter {
+            mode,
+            k: 2.0,
+            a1: 0.0,
+            a2: 0.0,
+            a3: 0.0,
+            ic1eq: [0.0; 2],
+            ic2eq: [0.0; 2],
+        };
+
+        filter.set_cutoff(1000.0, 0.0);
+        return filter;
+    }
+
+    pub fn set_mode(&mut self, mode: FilterMode) {
+        self.mode = mode;
+    }
+
+    // Resonance from 0.0 to 1.0, Q goes from 0.5 to 20
+    pub fn set_cutoff(&mut self, cutoff: f32, resonance: f32) {
+        let g = (PI * cutoff.clamp(MIN_CUTOFF, MAX_CUTOFF) / SAMPLE_RATE).tan();
+        self.k = 2.0 - 1.95 * resonance.clamp(0.0, 1.0);
+
+        self.a1 = 1.0 / (1.0 + g * (g + self.k));
+        self.a2 = g * self.a1;
+        self.a3 = g * self.a2;
+    }
+
+    pub fn process(&mut self, input: f32, channel: usize) -> f32 {
+        let v3 = input - self.ic2eq[channel];
+        let v1 = self.a1 * self.ic1eq[channel] + self.a2 * v3;
+        let v2 = self.ic2eq[channel] + self.a2 * self.ic1eq[channel] + self.a3 * v3;
+
+        self.ic1eq[channel] = 2.0 * v1 - self.ic1eq[channel];
+        self.ic2eq[channel] = 2.0 * v2 - self.ic2eq[channel];
+
+        let low = v2;
+        let band = v1;
+        let high = input - self.k * band - low;
+
+        return match self.mode {
+            // The ladder is a filter of its own
+            FilterMode::Off | FilterMode::Ladder => input,
+            FilterMode::LowPass => low,
+            FilterMode::HighPass => high,
+            FilterMode::BandPass => band,
+            FilterMode::Notch => low + high,
+        };
+    }
+
+    pub fn process_frame(&mut self, frame: [f32; 2]) -> [f32; 2] {
+        return [self.process(frame[0], 0), self.process(frame[1], 1)];
+    }
+}
+
+// Moog style 24dB/oct ladder of four zero delay feedback one pole low passes. The feedback is
+// solved for each sample as if the ladder were linear, then saturated with tanh, so quiet
+// signals follow the analog response exactly and the self oscillation at full resonance is
+// held at a steady level. Drive boosts the input into the saturation and the output is scaled
+// back down by the same amount.
+pub struct LadderFilter {
+    // Feedback, the ladder self oscillates from 4.0
+    k: f32,
+    // One pole gain g / (1 + g)
+    g: f32,
+    drive_gain: f32,
+    // Integrator states of the four stages for the left and right channels
+    states: [[f32; 4]; 2],
+}
+
+impl LadderFilter {
+    pub fn new() -> LadderFilter {
+        let mut filter = LadderFilter {
+            k: 0.0,
+            g: 0.0,
+            drive_gain: 1.0,
+            states: [[0.0; 4]; 2],
+        };
+
+        filter.set_cutoff(1000.0, 0.0);
+        return filter;
+    }
+
+    // Resonance from 0.0 to 1.0, self oscillating above 0.95
+    pub fn set_cutoff(&mut self, cutoff: f32, resonance: f32) {
+        let g = (PI * cutoff.clamp(MIN_CUTOFF, MAX_CUTOFF) / SAMPLE_RATE).tan();
+        self.g = g / (1.0 + g);
+        self.k = 4.2 * resonance.clamp(0.0, 1.0);
+    }
+
+    // Drive in dB
+    pub fn set_drive(&mut self, drive: f32) {
+        self.drive_gain = (10.0 as f32).powf(drive.max(0.0) / 20.0);
+    }
+
+    pub fn process(&mut self, input: f32, channel: usize) -> f32 {
+        let g = self.g;
+        let states = &mut self.states[channel];
+
+        // Each stage outputs g * input + (1 - g) * state, so the last one is g^4 times the ladder's
+        // input plus what the states contribute
+        let state_sum = (1.0 - g) * (g * g * g * states[0] + g * g * states[1] + g * states[2] + states[3]);
+        let feedback_input = (input * self.drive_gain - self.k * state_sum) / (1.0 + self.k * g * g * g * g);
+
+        let mut stage_input = feedback_input.tanh();
+        for state in states.iter_mut() {
+            let v = g * (stage_input - *state);
+            stage_input = v + *state;
+            *state = stage_input + v;
+        }
+
+        return stage_input / self.drive_gain;
+    }
+
+    pub fn process_frame(&mut self, frame: [f32; 2]) -> [f32; 2] {
+        return [self.process(frame[0], 0), self.process(frame[1], 1)];
+    }
+}
@@ -11,4 +11,13 @@ pub mod note_generator;
 pub mod sound_generator;
 pub mod wavetables;
 pub mod wavetype;
-pub mod constants;
\ No newline at end of file
+pub mod constants;
+pub mod random;
+pub mod noise;
+pub mod wav;
+pub mod midi_file;
+pub mod preset;
+pub mod midi;
+pub mod tuning;
+pub mod scala;
+pub mod filter;
@@ -1,11 +1,13 @@
 use std::cell::RefCell;
+use std::fs::File;
 use std::rc::Rc;
 use std::str::FromStr;
+use std::sync::Arc;
 use std::sync::mpsc::*;
 
 use egui::*;
 use egui_plot::{Line, Plot, PlotPoints};
-use rodio::{OutputStream, Sink};
+use rodio::{OutputStream, Sink, Source};
 use eframe::{run_native, App, NativeOptions, egui};
 
 slint::include_modules!();
@@ -15,8 +17,14 @@ use strum::{EnumCount, IntoEnumIterator, VariantNames};
 
 use oxidizer::wavetables::*;
 use oxidizer::constants::*;
-use oxidizer::wavetype::WaveType;
-use oxidizer::synthesizer::{Synthesizer, SynthEvent, EnvelopeParam};
+use oxidizer::wavetype::{WaveType, OscillatorQuality, PhaseMode, OscModulation, FmAlgorithm, FilterMode, EnvelopeDestination, EnvelopeCurve, EnvelopeStage};
+use oxidizer::wav::{WavRecorder, WavFormat};
+use oxidizer::midi_file::{MidiFile, MidiFilePlayer};
+use oxidizer::midi;
+use oxidizer::scala::{Scale, KeyboardMapping};
+use oxidizer::synthesizer::{Synthesizer, SynthEvent, EnvelopeParam, TuningParam};
+use oxidizer::tuning::DEFAULT_A4_FREQUENCY;
+use oxidizer::envelope::{DEFAULT_START_AMPLITUDE, DEFAULT_SUSTAIN_AMPLITUDE};
 
 struct OxidizerApp {
     current_notes: Vec<i32>,
@@ -25,7 +33,17 @@ struct OxidizerApp {
     attack: f32,
     decay: f32,
     release: f32,
+    sustain: f32,
+    envelope_shapes: [EnvelopeShape; EnvelopeStage::COUNT],
     lfo: LfoParams,
+    fm: FmParams,
+    sub_osc: SubOscParams,
+    filter: FilterParams,
+    mod_envelopes: [ModEnvelopeParams; ModEnvelopeNumber::COUNT],
+    velocity: VelocityParams,
+    a4_frequency: f32,
+    transpose: f32,
+    fine_tune: f32,
 
     synth_sender: Sender<SynthEvent>
 }
@@ -39,7 +57,17 @@ impl OxidizerApp{
             attack: 0.1,
             decay: 1.0,
             release: 0.1,
+            sustain: DEFAULT_SUSTAIN_AMPLITUDE,
+            envelope_shapes: [EnvelopeShape::default(); EnvelopeStage::COUNT],
             lfo: Default::default(),
+            fm: Default::default(),
+            sub_osc: Default::default(),
+            filter: Default::default(),
+            mod_envelopes: ModEnvelopeParams::create_default_array(),
+            velocity: Default::default(),
+            a4_frequency: DEFAULT_A4_FREQUENCY,
+            transpose: 0.0,
+            fine_tune: 0.0,
             synth_sender: sender
         }
     }
@@ -53,14 +81,14 @@ impl OxidizerApp{
                 if ctx.input(|input| 
                         oxidizer::keyboard::is_key_pressed(input, key) || 
                         oxidizer::keyboard::is_key_down(input, key)) {
-                    self.new_notes.push(i as i32);
+                    self.new_notes.push(KEYBOARD_BASE_NOTE + i as i32);
                 }
             }
         }
 
         for i in &self.new_notes {
             if !self.current_notes.contains(i) {
-                let _ = self.synth_sender.send(SynthEvent::NotePress(*i));               
+                let _ = self.synth_sender.send(SynthEvent::NotePress(*i, DEFAULT_VELOCITY));               
             }
         }   
 
@@ -141,6 +169,261 @@ impl OxidizerApp{
                 let _ = self.synth_sender.send(SynthEvent::ChangeLfoParams(self.lfo.clone()));
             }
             ui.end_row();
+
+            ui.label("");
+            if ui.checkbox(&mut self.lfo.vibrato, "Vibrato").changed() {
+                let _ = self.synth_sender.send(SynthEvent::ChangeLfoParams(self.lfo.clone()));
+            }
+            ui.end_row();
+        }
+
+        ui.separator();
+        ui.end_row();
+    }
+
+    fn render_sub_osc(&mut self, ui: &mut Ui){
+
+        if ui.checkbox(&mut self.sub_osc.enabled, "Sub Oscillator").changed() {
+            let _ = self.synth_sender.send(SynthEvent::ChangeSubOscParams(self.sub_osc.clone()));
+        }
+        ui.end_row();
+
+        if self.sub_osc.enabled {
+            ui.label("Wave Form:");
+            ui.horizontal(|ui| {
+                for wave_type in [WaveType::Square, WaveType::Sin] {
+                    let display_str: &'static str = wave_type.into();
+                    if ui.selectable_value(&mut self.sub_osc.wave_type, wave_type, display_str).changed() {
+                        let _ = self.synth_sender.send(SynthEvent::ChangeSubOscParams(self.sub_osc.clone()));
+                    }
+                }
+
+                for octave in [-1, -2] {
+                    if ui.selectable_value(&mut self.sub_osc.octave, octave, format!("{octave} oct")).changed() {
+                        let _ = self.synth_sender.send(SynthEvent::ChangeSubOscParams(self.sub_osc.clone()));
+                    }
+                }
+            });
+            ui.end_row();
+
+            ui.label("Level:");
+            let slider = Slider::new(&mut self.sub_osc.gain, -48.0..=6.0)
+                .custom_formatter(|n, _| {
+                    format!("{n:.1}dB")
+                });
+
+            if ui.add(slider).changed() {
+                let _ = self.synth_sender.send(SynthEvent::ChangeSubOscParams(self.sub_osc.clone()));
+            }
+            ui.end_row();
+        }
+
+        ui.separator();
+        ui.end_row();
+    }
+
+    fn render_filter(&mut self, ui: &mut Ui){
+
+        ui.label(RichText::new("Filter").underline());
+        ui.end_row();
+
+        ui.label("Mode:");
+        ui.horizontal(|ui| {
+            for mode in FilterMode::iter(){
+                let display_str: &'static str = mode.into();
+                if ui.selectable_value(&mut self.filter.mode, mode, display_str).changed() {
+                    let _ = self.synth_sender.send(SynthEvent::ChangeFilterMode(self.filter.mode));
+                }
+            }
+        });
+        ui.end_row();
+
+        if self.filter.mode != FilterMode::Off {
+            ui.label("Cutoff:");
+            let slider = Slider::new(&mut self.filter.cutoff, 20.0..=20000.0)
+                .logarithmic(true)
+                .custom_formatter(|n, _| {
+                    format!("{n:.0}Hz")
+                });
+
+            if ui.add(slider).changed() {
+                let _ = self.synth_sender.send(SynthEvent::ChangeFilter(FilterParam::Cutoff, self.filter.cutoff));
+            }
+            ui.end_row();
+
+            ui.label("Resonance:");
+            let slider = Slider::new(&mut self.filter.resonance, 0.0..=1.0)
+                .fixed_decimals(2);
+
+            if ui.add(slider).changed() {
+                let _ = self.synth_sender.send(SynthEvent::ChangeFilter(FilterParam::Resonance, self.filter.resonance));
+            }
+            ui.end_row();
+
+            if self.filter.mode == FilterMode::Ladder {
+                ui.label("Drive:");
+                let slider = Slider::new(&mut self.filter.drive, 0.0..=24.0)
+                    .custom_formatter(|n, _| {
+                        format!("{n:.1}dB")
+                    });
+
+                if ui.add(slider).changed() {
+                    let _ = self.synth_sender.send(SynthEvent::ChangeFilter(FilterParam::Drive, self.filter.drive));
+                }
+                ui.end_row();
+            }
+
+            ui.label("Key Tracking:");
+            let slider = Slider::new(&mut self.filter.key_tracking, 0.0..=1.0)
+                .custom_formatter(|n, _| {
+                    let i = (n * 100.0).round() as i64;
+                    format!("{i}%")
+                });
+
+            if ui.add(slider).changed() {
+                let _ = self.synth_sender.send(SynthEvent::ChangeFilter(FilterParam::KeyTracking, self.filter.key_tracking));
+            }
+            ui.end_row();
+
+            ui.label("Velocity Amount:");
+            let slider = Slider::new(&mut self.filter.velocity_amount, -8.0..=8.0)
+                .custom_formatter(|n, _| {
+                    format!("{n:.1} oct")
+                });
+
+            if ui.add(slider).changed() {
+                let _ = self.synth_sender.send(SynthEvent::ChangeFilter(FilterParam::VelocityAmount, self.filter.velocity_amount));
+            }
+            ui.end_row();
+        }
+
+        ui.separator();
+        ui.end_row();
+    }
+
+    fn render_mod_envelopes(&mut self, ui: &mut Ui){
+
+        for mod_envelope in &mut self.mod_envelopes {
+            let display_str: &'static str = mod_envelope.num.into();
+            ui.label(RichText::new(format!("{display_str} Envelope")).underline());
+            ui.end_row();
+
+            ui.label("Destination:");
+            ui.horizontal(|ui| {
+                for destination in EnvelopeDestination::iter(){
+                    let display_str: &'static str = destination.into();
+                    if ui.selectable_value(&mut mod_envelope.destination, destination, display_str).changed() {
+                        let _ = self.synth_sender.send(SynthEvent::ChangeModEnvelopeParams(mod_envelope.clone()));
+                    }
+                }
+            });
+            ui.end_row();
+
+            ui.label("Amount:");
+            let (range, unit) = match mod_envelope.destination {
+                EnvelopeDestination::FilterCutoff => (-8.0..=8.0, " oct"),
+                EnvelopeDestination::Pitch => (-24.0..=24.0, " st"),
+                EnvelopeDestination::PulseWidth => (-0.5..=0.5, ""),
+                EnvelopeDestination::FmIndex => (-10.0..=10.0, ""),
+            };
+            let slider = Slider::new(&mut mod_envelope.amount, range)
+                .custom_formatter(|n, _| {
+                    format!("{n:.2}{unit}")
+                });
+
+            if ui.add(slider).changed() {
+                let _ = self.synth_sender.send(SynthEvent::ChangeModEnvelopeParams(mod_envelope.clone()));
+            }
+            ui.end_row();
+
+            for (name, stage) in [("Attack:", 0), ("Decay:", 1), ("Release:", 2)] {
+                ui.label(name);
+                let value = match stage {
+                    0 => &mut mod_envelope.attack,
+                    1 => &mut mod_envelope.decay,
+                    _ => &mut mod_envelope.release,
+                };
+
+                let slider = Slider::new(value, 0.0..=32.0)
+                    .logarithmic(true)
+                    .smallest_positive(0.001)
+                    .smart_aim(false)
+                    .min_decimals(1);
+
+                if ui.add(slider).changed() {
+                    let _ = self.synth_sender.send(SynthEvent::ChangeModEnvelopeParams(mod_envelope.clone()));
+                }
+                ui.end_row();
+            }
+
+            ui.label("Sustain:");
+            let slider = Slider::new(&mut mod_envelope.sustain, 0.0..=1.0)
+                .fixed_decimals(2);
+
+            if ui.add(slider).changed() {
+                let _ = self.synth_sender.send(SynthEvent::ChangeModEnvelopeParams(mod_envelope.clone()));
+            }
+            ui.end_row();
+
+            ui.separator();
+            ui.end_row();
+        }
+    }
+
+    fn render_fm(&mut self, ui: &mut Ui){
+
+        ui.label(RichText::new("FM").underline());
+        ui.end_row();
+
+        ui.label("Algorithm:");
+        ui.horizontal(|ui| {
+            for algorithm in FmAlgorithm::iter(){
+                let display_str: &'static str = algorithm.into();
+                if ui.selectable_value(&mut self.fm.algorithm, algorithm, display_str).changed() {
+                    let _ = self.synth_sender.send(SynthEvent::ChangeFmParams(self.fm.clone()));
+                }
+            }
+        });
+        ui.end_row();
+
+        if self.fm.algorithm != FmAlgorithm::Off {
+            ui.label("Index:");
+            let slider = Slider::new(&mut self.fm.index, 0.0..=10.0)
+                .fixed_decimals(1);
+
+            if ui.add(slider).changed() {
+                let _ = self.synth_sender.send(SynthEvent::ChangeFmParams(self.fm.clone()));
+            }
+            ui.end_row();
+
+            for (name, stage) in [("Index Attack:", 0), ("Index Decay:", 1), ("Index Release:", 2)] {
+                ui.label(name);
+                let value = match stage {
+                    0 => &mut self.fm.attack,
+                    1 => &mut self.fm.decay,
+                    _ => &mut self.fm.release,
+                };
+
+                let slider = Slider::new(value, 0.0..=32.0)
+                    .logarithmic(true)
+                    .smallest_positive(0.001)
+                    .smart_aim(false)
+                    .min_decimals(1);
+
+                if ui.add(slider).changed() {
+                    let _ = self.synth_sender.send(SynthEvent::ChangeFmParams(self.fm.clone()));
+                }
+                ui.end_row();
+            }
+
+            ui.label("Index Sustain:");
+            let slider = Slider::new(&mut self.fm.sustain, 0.0..=1.0)
+                .fixed_decimals(2);
+
+            if ui.add(slider).changed() {
+                let _ = self.synth_sender.send(SynthEvent::ChangeFmParams(self.fm.clone()));
+            }
+            ui.end_row();
         }
 
         ui.separator();
@@ -175,6 +458,18 @@ impl OxidizerApp{
         }
         ui.end_row();
 
+        ui.label("Sustain:");
+        let slider = Slider::new(&mut self.sustain, 0.0..=DEFAULT_START_AMPLITUDE)
+            .custom_formatter(|n, _| {
+                let i = (n / DEFAULT_START_AMPLITUDE as f64 * 100.0).round() as i64;
+                format!("{i}%")
+            });
+
+        if ui.add(slider).changed() {
+            let _ = self.synth_sender.send(SynthEvent::ChangeEnvelope(EnvelopeParam::SustainAmplitude, self.sustain));
+        }
+        ui.end_row();
+
         ui.label("Release:");
         let slider = Slider::new(&mut self.release, 0.0..=32.0)
             .logarithmic(true)
@@ -187,6 +482,107 @@ impl OxidizerApp{
         }
         ui.end_row();
 
+        for stage in EnvelopeStage::iter() {
+            let display_str: &'static str = stage.into();
+            ui.label(format!("{display_str} Curve:"));
+
+            let shape = &mut self.envelope_shapes[stage as usize];
+            ui.horizontal(|ui| {
+                let mut changed = false;
+                for curve in EnvelopeCurve::iter(){
+                    let display_str: &'static str = curve.into();
+                    changed |= ui.selectable_value(&mut shape.curve, curve, display_str).changed();
+                }
+
+                if shape.curve != EnvelopeCurve::Linear {
+                    let slider = Slider::new(&mut shape.tension, 0.0..=1.0)
+                        .text("Tension")
+                        .fixed_decimals(2);
+                    changed |= ui.add(slider).changed();
+                }
+
+                if changed {
+                    let _ = self.synth_sender.send(SynthEvent::ChangeEnvelopeShape(stage, *shape));
+                }
+            });
+            ui.end_row();
+        }
+
+        ui.separator();
+        ui.end_row();
+    }
+
+    fn render_velocity(&mut self, ui: &mut Ui){
+
+        ui.label(RichText::new("Velocity").underline());
+        ui.end_row();
+        ui.label("Curve:");
+        ui.horizontal(|ui| {
+            for curve in VelocityCurve::iter(){
+                let display_str: &'static str = curve.into();
+                if ui.selectable_value(&mut self.velocity.curve, curve, display_str).changed() {
+                    let _ = self.synth_sender.send(SynthEvent::ChangeVelocityParams(self.velocity.clone()));
+                }
+            }
+        });
+        ui.end_row();
+
+        ui.label("Amplitude:");
+        let slider = Slider::new(&mut self.velocity.amplitude_amount, 0.0..=1.0)
+            .fixed_decimals(2);
+        
+        if ui.add(slider).changed() {
+            let _ = self.synth_sender.send(SynthEvent::ChangeVelocityParams(self.velocity.clone()));
+        }
+        ui.end_row();
+
+        ui.label("Attack:");
+        let slider = Slider::new(&mut self.velocity.attack_amount, 0.0..=1.0)
+            .fixed_decimals(2);
+        
+        if ui.add(slider).changed() {
+            let _ = self.synth_sender.send(SynthEvent::ChangeVelocityParams(self.velocity.clone()));
+        }
+        ui.end_row();
+
+        ui.separator();
+        ui.end_row();
+    }
+
+    fn render_tuning(&mut self, ui: &mut Ui){
+
+        ui.label(RichText::new("Tuning").underline());
+        ui.end_row();
+        ui.label("A4:");
+        let slider = Slider::new(&mut self.a4_frequency, 400.0..=480.0)
+            .fixed_decimals(1)
+            .suffix("Hz");
+        
+        if ui.add(slider).changed() {
+            let _ = self.synth_sender.send(SynthEvent::ChangeTuning(TuningParam::ReferenceFrequency, self.a4_frequency));
+        }
+        ui.end_row();
+
+        ui.label("Transpose:");
+        let slider = Slider::new(&mut self.transpose, -24.0..=24.0)
+            .step_by(1.0)
+            .fixed_decimals(0);
+        
+        if ui.add(slider).changed() {
+            let _ = self.synth_sender.send(SynthEvent::ChangeTuning(TuningParam::Transpose, self.transpose));
+        }
+        ui.end_row();
+
+        ui.label("Fine Tune:");
+        let slider = Slider::new(&mut self.fine_tune, -100.0..=100.0)
+            .fixed_decimals(0)
+            .suffix("c");
+        
+        if ui.add(slider).changed() {
+            let _ = self.synth_sender.send(SynthEvent::ChangeTuning(TuningParam::FineTune, self.fine_tune));
+        }
+        ui.end_row();
+
         ui.separator();
         ui.end_row();
     }
@@ -219,6 +615,39 @@ impl OxidizerApp{
             ui.end_row();
 
             if osc_params.enabled {
+                ui.label("Quality:");
+                ui.horizontal(|ui| {
+                    for quality in OscillatorQuality::iter(){
+                        let display_str: &'static str = quality.into();
+                        if ui.selectable_value(&mut osc_params.quality, quality, display_str).changed() {
+                            let _ = self.synth_sender.send(SynthEvent::ChangeSoundGenOscParams(osc_params.clone()));
+                        }
+                    }
+                });
+                ui.end_row();
+            }
+
+            if osc_params.enabled {
+                ui.label("Modulation:");
+                ui.horizontal(|ui| {
+                    for modulation in OscModulation::iter(){
+                        let display_str: &'static str = modulation.into();
+                        if ui.selectable_value(&mut osc_params.modulation, modulation, display_str).changed() {
+                            let _ = self.synth_sender.send(SynthEvent::ChangeSoundGenOscParams(osc_params.clone()));
+                        }
+                    }
+
+                    if osc_params.modulation != OscModulation::Off {
+                        ui.label("by");
+                        for modulator in OscNumber::iter().filter(|num| *num != osc_params.num){
+                            let display_str: &'static str = modulator.into();
+                            if ui.selectable_value(&mut osc_params.modulator, modulator, display_str).changed() {
+                                let _ = self.synth_sender.send(SynthEvent::ChangeSoundGenOscParams(osc_params.clone()));
+                            }
+                        }
+                    }
+                });
+                ui.end_row();
 
                 ui.label("Unisons:");
                 ui.group(|ui| {
@@ -232,18 +661,147 @@ impl OxidizerApp{
                         let _ = self.synth_sender.send(SynthEvent::ChangeSoundGenOscParams(osc_params.clone()));
                     }
 
-                    let slider = Slider::new(&mut osc_params.unison_detune_pct, 0.0..=1.0)
-                        .fixed_decimals(2)
+                    let slider = Slider::new(&mut osc_params.unison_detune_pct, 0.0..=1.0)
+                        .fixed_decimals(2)
+                        .custom_formatter(|n, _| {
+                            let i = (n * 100.0).round() as i64;
+                            format!("{i}%")
+                        });
+                    
+                    if ui.add(slider).changed() {
+                        let _ = self.synth_sender.send(SynthEvent::ChangeSoundGenOscParams(osc_params.clone()));
+                    }
+
+                    let slider = Slider::new(&mut osc_params.unison_spread, 0.0..=1.0)
+                        .custom_formatter(|n, _| {
+                            let i = (n * 100.0).round() as i64;
+                            format!("Spread {i}%")
+                        });
+
+                    if ui.add(slider).changed() {
+                        let _ = self.synth_sender.send(SynthEvent::ChangeOscillator(osc_params.num, OscillatorParam::UnisonSpread, osc_params.unison_spread));
+                    }
+                });
+                ui.end_row();
+
+                ui.label("Phase:");
+                ui.horizontal(|ui| {
+                    for phase_mode in PhaseMode::iter(){
+                        let display_str: &'static str = phase_mode.into();
+                        if ui.selectable_value(&mut osc_params.phase_mode, phase_mode, display_str).changed() {
+                            let _ = self.synth_sender.send(SynthEvent::ChangeSoundGenOscParams(osc_params.clone()));
+                        }
+                    }
+
+                    if osc_params.phase_mode == PhaseMode::Fixed {
+                        let slider = Slider::new(&mut osc_params.start_phase, 0.0..=1.0)
+                            .custom_formatter(|n, _| {
+                                let degrees = (n * 360.0).round() as i64;
+                                format!("{degrees}°")
+                            });
+
+                        if ui.add(slider).changed() {
+                            let _ = self.synth_sender.send(SynthEvent::ChangeOscillator(osc_params.num, OscillatorParam::StartPhase, osc_params.start_phase));
+                        }
+                    }
+                });
+                ui.end_row();
+
+                ui.label("Level:");
+                ui.group(|ui| {
+                    let slider = Slider::new(&mut osc_params.gain, -48.0..=6.0)
+                        .custom_formatter(|n, _| {
+                            format!("{n:.1}dB")
+                        });
+
+                    if ui.add(slider).changed() {
+                        let _ = self.synth_sender.send(SynthEvent::ChangeOscillator(osc_params.num, OscillatorParam::Gain, osc_params.gain));
+                    }
+
+                    let slider = Slider::new(&mut osc_params.pan, -1.0..=1.0)
+                        .custom_formatter(|n, _| {
+                            let i = (n * 100.0).round() as i64;
+                            format!("Pan {i}")
+                        });
+
+                    if ui.add(slider).changed() {
+                        let _ = self.synth_sender.send(SynthEvent::ChangeOscillator(osc_params.num, OscillatorParam::Pan, osc_params.pan));
+                    }
+                });
+                ui.end_row();
+
+                ui.label("Pitch:");
+                ui.group(|ui| {
+                    let slider = Slider::new(&mut osc_params.octave, -3..=3)
+                        .integer()
+                        .custom_formatter(|n, _| {
+                            format!("{n}oct")
+                        });
+
+                    if ui.add(slider).changed() {
+                        let _ = self.synth_sender.send(SynthEvent::ChangeOscillator(osc_params.num, OscillatorParam::Octave, osc_params.octave as f32));
+                    }
+
+                    let slider = Slider::new(&mut osc_params.semitone, -12..=12)
+                        .integer()
+                        .custom_formatter(|n, _| {
+                            format!("{n}st")
+                        });
+
+                    if ui.add(slider).changed() {
+                        let _ = self.synth_sender.send(SynthEvent::ChangeOscillator(osc_params.num, OscillatorParam::Semitone, osc_params.semitone as f32));
+                    }
+
+                    let slider = Slider::new(&mut osc_params.fine_tune_cents, -100.0..=100.0)
+                        .custom_formatter(|n, _| {
+                            format!("{n:.0}c")
+                        });
+
+                    if ui.add(slider).changed() {
+                        let _ = self.synth_sender.send(SynthEvent::ChangeOscillator(osc_params.num, OscillatorParam::FineTune, osc_params.fine_tune_cents));
+                    }
+                });
+                ui.end_row();
+
+                if osc_params.wave_type == WaveType::Pulse && osc_params.wavetable.is_none() {
+                    ui.label("Pulse Width:");
+                    ui.group(|ui| {
+                        let slider = Slider::new(&mut osc_params.pulse_width, MIN_PULSE_WIDTH..=MAX_PULSE_WIDTH)
+                            .custom_formatter(|n, _| {
+                                let i = (n * 100.0).round() as i64;
+                                format!("{i}%")
+                            });
+
+                        if ui.add(slider).changed() {
+                            let _ = self.synth_sender.send(SynthEvent::ChangeSoundGenOscParams(osc_params.clone()));
+                        }
+
+                        let slider = Slider::new(&mut osc_params.pwm_depth, 0.0..=0.5)
+                            .custom_formatter(|n, _| {
+                                let i = (n * 100.0).round() as i64;
+                                format!("PWM {i}%")
+                            });
+
+                        if ui.add(slider).changed() {
+                            let _ = self.synth_sender.send(SynthEvent::ChangeSoundGenOscParams(osc_params.clone()));
+                        }
+                    });
+                    ui.end_row();
+                }
+
+                if osc_params.wavetable.is_some() {
+                    ui.label("Position:");
+                    let slider = Slider::new(&mut osc_params.wavetable_position, 0.0..=1.0)
                         .custom_formatter(|n, _| {
                             let i = (n * 100.0).round() as i64;
                             format!("{i}%")
                         });
-                    
+
                     if ui.add(slider).changed() {
                         let _ = self.synth_sender.send(SynthEvent::ChangeSoundGenOscParams(osc_params.clone()));
                     }
-                });
-                ui.end_row();
+                    ui.end_row();
+                }
 
                 Self::plot_oscillator(ui, format!("Oscillator {display_num} Wave Form"), &osc_params.wave_type);
                 ui.end_row();
@@ -257,8 +815,14 @@ impl OxidizerApp{
     fn render_grid(&mut self, ui: &mut Ui){
 
         self.render_oscillators(ui);
+        self.render_sub_osc(ui);
+        self.render_filter(ui);
         self.render_envelope(ui);
+        self.render_mod_envelopes(ui);
+        self.render_velocity(ui);
+        self.render_tuning(ui);
         self.render_lfo(ui);
+        self.render_fm(ui);
 
     }
 
@@ -302,7 +866,51 @@ fn main() {
 
     let synth = Synthesizer::new(synth_receiver);
 
-    sink.append(synth);
+    let args: Vec<String> = std::env::args().collect();
+    let get_arg = |name: &str| args.iter().position(|arg| arg == name).and_then(|i| args.get(i + 1));
+
+    // `oxidizer --play <file.mid>` plays a MIDI file through the synth alongside live input
+    let mut source: Box<dyn Source<Item = f32> + Send> = match get_arg("--play") {
+        Some(path) => {
+            let midi_file = MidiFile::from_file(path).expect("Failed to load MIDI file");
+            Box::new(MidiFilePlayer::new(&midi_file, synth))
+        },
+        None => Box::new(synth),
+    };
+
+    // `oxidizer --record <file.wav>` bounces everything played to disk
+    let mut recording = None;
+    if let Some(path) = get_arg("--record") {
+        let (recorder, wav_recording) = WavRecorder::new(source, path, WavFormat::Int16, true).expect("Failed to create recording file");
+        source = Box::new(recorder);
+        recording = Some(wav_recording);
+    }
+
+    sink.append(source);
+
+    // `oxidizer --scl <file.scl> [--kbm <file.kbm>]` tunes the synth to a Scala scale
+    if let Some(path) = get_arg("--scl") {
+        let scale = Scale::from_file(path).unwrap_or_else(|error| panic!("Failed to load scale {}: {}", path, error));
+        ui_sender.send(SynthEvent::ChangeScale(Some(scale))).unwrap();
+    }
+
+    if let Some(path) = get_arg("--kbm") {
+        let keyboard_mapping = KeyboardMapping::from_file(path).unwrap_or_else(|error| panic!("Failed to load keyboard mapping {}: {}", path, error));
+        ui_sender.send(SynthEvent::ChangeKeyboardMapping(Some(keyboard_mapping))).unwrap();
+    }
+
+    // `oxidizer --midi-in <path>` plays raw MIDI from a device or pipe, e.g. /dev/snd/midiC1D0
+    if let Some(path) = get_arg("--midi-in") {
+        let midi_input = File::open(path).expect("Failed to open MIDI input");
+        let midi_sender = ui_sender.clone();
+
+        std::thread::spawn(move || {
+            if let Err(error) = midi::send_synth_events(midi_input, &midi_sender) {
+                eprintln!("MIDI input stopped: {}", error);
+            }
+        });
+    }
+
     sink.play();
 
     // env_logger::init(); 
@@ -323,21 +931,68 @@ fn main() {
 
     let app = Rc::new(RefCell::new(OxidizerApp::default(ui_sender)));
 
+    // `oxidizer --wavetable <file.wav>` plays a single cycle or multi frame wavetable on oscillator 1
+    if let Some(path) = get_arg("--wavetable") {
+        let wavetable = WaveTable::from_wav_file(path, None).unwrap_or_else(|error| panic!("Failed to load wavetable {}: {}", path, error));
+        let app = &mut app.borrow_mut();
+
+        let params = &mut app.sound_gen_oscillators[0];
+        params.wavetable = Some(Arc::new(wavetable));
+
+        let event = SynthEvent::ChangeSoundGenOscParams(params.clone());
+        let _ = app.synth_sender.send(event);
+    }
+
     let wave_types: Vec<SharedString> = WaveType::VARIANTS
         .iter()
         .map(|i| {
             (*i).to_string().into()
         }).collect();
     window.set_osc_wave_types(ModelRc::from(Rc::new(VecModel::from(wave_types))));
+
+    let qualities: Vec<SharedString> = OscillatorQuality::VARIANTS
+        .iter()
+        .map(|i| {
+            (*i).to_string().into()
+        }).collect();
+    window.set_osc_qualities(ModelRc::from(Rc::new(VecModel::from(qualities))));
+
+    let phase_modes: Vec<SharedString> = PhaseMode::VARIANTS
+        .iter()
+        .map(|i| {
+            (*i).to_string().into()
+        }).collect();
+    window.set_osc_phase_modes(ModelRc::from(Rc::new(VecModel::from(phase_modes))));
+
+    let modulations: Vec<SharedString> = OscModulation::VARIANTS
+        .iter()
+        .map(|i| {
+            (*i).to_string().into()
+        }).collect();
+    window.set_osc_modulations(ModelRc::from(Rc::new(VecModel::from(modulations))));
+
+    let osc_numbers: Vec<SharedString> = OscNumber::VARIANTS
+        .iter()
+        .map(|i| {
+            (*i).to_string().into()
+        }).collect();
+    window.set_osc_numbers(ModelRc::from(Rc::new(VecModel::from(osc_numbers))));
+
+    let filter_modes: Vec<SharedString> = FilterMode::VARIANTS
+        .iter()
+        .map(|i| {
+            (*i).to_string().into()
+        }).collect();
+    window.set_filter_modes(ModelRc::from(Rc::new(VecModel::from(filter_modes))));
     
     let clone = app.clone();
     window.global::<KeyPress>().on_key_pressed(move |value| {
-        let _ = clone.borrow().synth_sender.send(SynthEvent::NotePress(value.parse::<i32>().unwrap().clone()));  
+        let _ = clone.borrow().synth_sender.send(SynthEvent::NotePress(KEYBOARD_BASE_NOTE + value.parse::<i32>().unwrap(), DEFAULT_VELOCITY));  
     });
 
     let clone = app.clone();
     window.global::<KeyPress>().on_key_released(move |value| {
-        let _ = clone.borrow().synth_sender.send(SynthEvent::NoteRelease(value.parse::<i32>().unwrap().clone())); 
+        let _ = clone.borrow().synth_sender.send(SynthEvent::NoteRelease(KEYBOARD_BASE_NOTE + value.parse::<i32>().unwrap())); 
     });
 
     let clone = app.clone();
@@ -354,6 +1009,74 @@ fn main() {
         }
     });
 
+    let clone = app.clone();
+    window.global::<KeyPress>().on_selected_quality(move |index, opt| {
+        
+        if let Ok(quality) = OscillatorQuality::from_str(&opt) {
+            let app = &mut clone.borrow_mut();
+
+            let params = &mut app.sound_gen_oscillators[index as usize];
+            params.quality = quality;
+
+            let event = SynthEvent::ChangeSoundGenOscParams(params.clone());
+            let _ = app.synth_sender.send(event);
+        }
+    });
+
+    let clone = app.clone();
+    window.global::<KeyPress>().on_selected_phase_mode(move |index, opt| {
+        
+        if let Ok(phase_mode) = PhaseMode::from_str(&opt) {
+            let app = &mut clone.borrow_mut();
+
+            let params = &mut app.sound_gen_oscillators[index as usize];
+            params.phase_mode = phase_mode;
+
+            let event = SynthEvent::ChangeSoundGenOscParams(params.clone());
+            let _ = app.synth_sender.send(event);
+        }
+    });
+
+    let clone = app.clone();
+    window.global::<KeyPress>().on_selected_modulation(move |index, opt| {
+        
+        if let Ok(modulation) = OscModulation::from_str(&opt) {
+            let app = &mut clone.borrow_mut();
+
+            let params = &mut app.sound_gen_oscillators[index as usize];
+            params.modulation = modulation;
+
+            let event = SynthEvent::ChangeSoundGenOscParams(params.clone());
+            let _ = app.synth_sender.send(event);
+        }
+    });
+
+    let clone = app.clone();
+    window.global::<KeyPress>().on_selected_modulator(move |index, opt| {
+        
+        if let Ok(modulator) = OscNumber::from_str(&opt) {
+            let app = &mut clone.borrow_mut();
+
+            let params = &mut app.sound_gen_oscillators[index as usize];
+            params.modulator = modulator;
+
+            let event = SynthEvent::ChangeSoundGenOscParams(params.clone());
+            let _ = app.synth_sender.send(event);
+        }
+    });
+
+    let clone = app.clone();
+    window.global::<KeyPress>().on_changed_start_phase(move |index, value| {
+        let app = &mut clone.borrow_mut();
+
+        let params = &mut app.sound_gen_oscillators[index as usize];
+        params.start_phase = value as f32 / 360.0;
+
+        let event = SynthEvent::ChangeOscillator(params.num, OscillatorParam::StartPhase, params.start_phase);
+        let _ = app.synth_sender.send(event);
+
+    });
+
     let clone = app.clone();
     window.global::<KeyPress>().on_changed_unison_voices(move |index, value| {
         let app = &mut clone.borrow_mut();
@@ -378,6 +1101,194 @@ fn main() {
         
     });
 
+    let clone = app.clone();
+    window.global::<KeyPress>().on_changed_unison_spread(move |index, value| {
+        let app = &mut clone.borrow_mut();
+
+        let params = &mut app.sound_gen_oscillators[index as usize];
+        params.unison_spread = value as f32 / 100.0;
+
+        let event = SynthEvent::ChangeOscillator(params.num, OscillatorParam::UnisonSpread, params.unison_spread);
+        let _ = app.synth_sender.send(event);
+
+    });
+
+    let clone = app.clone();
+    window.global::<KeyPress>().on_changed_osc_gain(move |index, value| {
+        let app = &mut clone.borrow_mut();
+
+        let params = &mut app.sound_gen_oscillators[index as usize];
+        params.gain = value as f32;
+
+        let event = SynthEvent::ChangeOscillator(params.num, OscillatorParam::Gain, params.gain);
+        let _ = app.synth_sender.send(event);
+
+    });
+
+    let clone = app.clone();
+    window.global::<KeyPress>().on_changed_osc_pan(move |index, value| {
+        let app = &mut clone.borrow_mut();
+
+        let params = &mut app.sound_gen_oscillators[index as usize];
+        params.pan = value as f32 / 100.0;
+
+        let event = SynthEvent::ChangeOscillator(params.num, OscillatorParam::Pan, params.pan);
+        let _ = app.synth_sender.send(event);
+
+    });
+
+    let clone = app.clone();
+    window.global::<KeyPress>().on_changed_osc_octave(move |index, value| {
+        let app = &mut clone.borrow_mut();
+
+        let params = &mut app.sound_gen_oscillators[index as usize];
+        params.octave = value;
+
+        let event = SynthEvent::ChangeOscillator(params.num, OscillatorParam::Octave, value as f32);
+        let _ = app.synth_sender.send(event);
+
+    });
+
+    let clone = app.clone();
+    window.global::<KeyPress>().on_changed_osc_semitone(move |index, value| {
+        let app = &mut clone.borrow_mut();
+
+        let params = &mut app.sound_gen_oscillators[index as usize];
+        params.semitone = value;
+
+        let event = SynthEvent::ChangeOscillator(params.num, OscillatorParam::Semitone, value as f32);
+        let _ = app.synth_sender.send(event);
+
+    });
+
+    let clone = app.clone();
+    window.global::<KeyPress>().on_changed_osc_fine_tune(move |index, value| {
+        let app = &mut clone.borrow_mut();
+
+        let params = &mut app.sound_gen_oscillators[index as usize];
+        params.fine_tune_cents = value as f32;
+
+        let event = SynthEvent::ChangeOscillator(params.num, OscillatorParam::FineTune, params.fine_tune_cents);
+        let _ = app.synth_sender.send(event);
+
+    });
+
+    let clone = app.clone();
+    window.global::<KeyPress>().on_changed_pulse_width(move |index, value| {
+        let app = &mut clone.borrow_mut();
+        
+        let params = &mut app.sound_gen_oscillators[index as usize];
+        params.pulse_width = value as f32 / 100.0;
+
+        let event = SynthEvent::ChangeSoundGenOscParams(params.clone());
+        let _ = app.synth_sender.send(event);
+        
+    });
+
+    let clone = app.clone();
+    window.global::<KeyPress>().on_changed_pwm_depth(move |index, value| {
+        let app = &mut clone.borrow_mut();
+        
+        let params = &mut app.sound_gen_oscillators[index as usize];
+        params.pwm_depth = value as f32 / 100.0;
+
+        let event = SynthEvent::ChangeSoundGenOscParams(params.clone());
+        let _ = app.synth_sender.send(event);
+        
+    });
+
+    let clone = app.clone();
+    window.global::<KeyPress>().on_changed_wavetable_position(move |index, value| {
+        let app = &mut clone.borrow_mut();
+        
+        let params = &mut app.sound_gen_oscillators[index as usize];
+        params.wavetable_position = value as f32 / 100.0;
+
+        let event = SynthEvent::ChangeSoundGenOscParams(params.clone());
+        let _ = app.synth_sender.send(event);
+        
+    });
+
+    let clone = app.clone();
+    window.global::<KeyPress>().on_sub_osc_enable_toggled(move || {
+        let app = &mut clone.borrow_mut();
+
+        app.sub_osc.enabled = !app.sub_osc.enabled;
+
+        let event = SynthEvent::ChangeSubOscParams(app.sub_osc.clone());
+        let _ = app.synth_sender.send(event);
+    });
+
+    let clone = app.clone();
+    window.global::<KeyPress>().on_selected_sub_osc_wave_form(move |opt| {
+
+        if let Ok(wave_type) = WaveType::from_str(&opt) {
+            let app = &mut clone.borrow_mut();
+
+            app.sub_osc.wave_type = wave_type;
+
+            let event = SynthEvent::ChangeSubOscParams(app.sub_osc.clone());
+            let _ = app.synth_sender.send(event);
+        }
+    });
+
+    let clone = app.clone();
+    window.global::<KeyPress>().on_selected_sub_osc_octave(move |opt| {
+
+        if let Ok(octave) = opt.parse::<i32>() {
+            let app = &mut clone.borrow_mut();
+
+            app.sub_osc.octave = octave;
+
+            let event = SynthEvent::ChangeSubOscParams(app.sub_osc.clone());
+            let _ = app.synth_sender.send(event);
+        }
+    });
+
+    let clone = app.clone();
+    window.global::<KeyPress>().on_changed_sub_osc_gain(move |value| {
+        let app = &mut clone.borrow_mut();
+
+        app.sub_osc.gain = value as f32;
+
+        let event = SynthEvent::ChangeSubOscParams(app.sub_osc.clone());
+        let _ = app.synth_sender.send(event);
+    });
+
+    let clone = app.clone();
+    window.global::<KeyPress>().on_selected_filter_mode(move |opt| {
+
+        if let Ok(mode) = FilterMode::from_str(&opt) {
+            let app = &mut clone.borrow_mut();
+
+            app.filter.mode = mode;
+
+            let event = SynthEvent::ChangeFilterMode(mode);
+            let _ = app.synth_sender.send(event);
+        }
+    });
+
+    let clone = app.clone();
+    window.global::<KeyPress>().on_changed_filter_cutoff(move |value| {
+        let app = &mut clone.borrow_mut();
+
+        // Same log scale as the slider's label
+        app.filter.cutoff = 20.0 * (1000.0 as f32).powf(value as f32 / 100.0);
+
+        let event = SynthEvent::ChangeFilter(FilterParam::Cutoff, app.filter.cutoff);
+        let _ = app.synth_sender.send(event);
+    });
+
+    let clone = app.clone();
+    window.global::<KeyPress>().on_changed_filter_resonance(move |value| {
+        let app = &mut clone.borrow_mut();
+
+        app.filter.resonance = value as f32 / 100.0;
+
+        let event = SynthEvent::ChangeFilter(FilterParam::Resonance, app.filter.resonance);
+        let _ = app.synth_sender.send(event);
+    });
+
     let clone = app.clone();
     window.global::<KeyPress>().on_osc_enable_toggled(move |index| {
         let app = &mut clone.borrow_mut();
@@ -391,6 +1302,10 @@ fn main() {
     });
 
     window.run().unwrap();
-    
-    
+
+    // The sink's mixer thread owns the recorder, so the file is finalized here rather than on drop.
+    // Write errors have already been reported by the writer thread.
+    if let Some(recording) = recording {
+        let _ = recording.finish();
+    }
 }
\ No newline at end of file
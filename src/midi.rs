@@ -0,0 +1,166 @@
+use std::io::{self, Read};
+use std::sync::mpsc::Sender;
+
+use crate::synthesizer::SynthEvent;
+
+const PITCH_BEND_CENTRE: i32 = 8192;
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum MidiMessage {
+    NoteOff { channel: u8, note: u8, velocity: u8 },
+    NoteOn { channel: u8, note: u8, velocity: u8 },
+    PolyPressure { channel: u8, note: u8, pressure: u8 },
+    ControlChange { channel: u8, controller: u8, value: u8 },
+    ProgramChange { channel: u8, program: u8 },
+    ChannelPressure { channel: u8, pressure: u8 },
+    // -8192 to 8191, 0 is centred
+    PitchBend { channel: u8, value: i16 },
+}
+
+impl MidiMessage {
+    // Messages the synth has no use for (e.g. poly pressure) map to None. All channels are
+    // played, the synth has no notion of MIDI channels.
+    pub fn to_synth_event(&self) -> Option<SynthEvent> {
+        return match *self {
+            MidiMessage::NoteOff { note, .. } => Some(SynthEvent::NoteRelease(note as i32)),
+            MidiMessage::NoteOn { note, velocity: 0, .. } => Some(SynthEvent::NoteRelease(note as i32)),
+            MidiMessage::NoteOn { note, velocity, .. } => Some(SynthEvent::NotePress(note as i32, velocity as f32 / 127.0)),
+            MidiMessage::ControlChange { controller, value, .. } => Some(SynthEvent::ControlChange(controller, value)),
+            MidiMessage::ProgramChange { program, .. } => Some(SynthEvent::ProgramChange(program)),
+            MidiMessage::ChannelPressure { pressure, .. } => Some(SynthEvent::ChannelPressure(pressure as f32 / 127.0)),
+            MidiMessage::PitchBend { value, .. } => Some(SynthEvent::PitchBend(value as f32 / PITCH_BEND_CENTRE as f32)),
+            MidiMessage::PolyPressure { .. } => None,
+        };
+    }
+}
+
+// Number of data bytes following a channel voice status byte
+fn get_data_length(status: u8) -> usize {
+    return match status & 0xF0 {
+        0xC0 | 0xD0 => 1,
+        _ => 2,
+    };
+}
+
+// Decodes a MIDI 1.0 byte stream one byte at a time, so it can sit behind any byte
+// source. Handles running status, ignores real time messages wherever they appear and
+// skips system exclusive and system common messages.
+pub struct MidiDecoder {
+    running_status: Option<u8>,
+    data: [u8; 2],
+    data_length: usize,
+    bytes_to_skip: usize,
+    in_sysex: bool,
+}
+
+impl MidiDecoder {
+    pub fn new() -> MidiDecoder {
+        return MidiDecoder {
+            running_status: None,
+            data: [0; 2],
+            data_length: 0,
+            bytes_to_skip: 0,
+            in_sysex: false,
+        };
+    }
+
+    pub fn feed(&mut self, byte: u8) -> Option<MidiMessage> {
+        // Real time messages can be interleaved anywhere, even inside other messages
+        if byte >= 0xF8 {
+            return None;
+        }
+
+        if byte & 0x80 != 0 {
+            return self.handle_status(byte);
+        }
+
+        if self.in_sysex {
+            return None;
+        }
+
+        if self.bytes_to_skip > 0 {
+            self.bytes_to_skip -= 1;
+            return None;
+        }
+
+        let status = self.running_status?;
+
+        self.data[self.data_length] = byte;
+        self.data_length += 1;
+
+        if self.data_length < get_data_length(status) {
+            return None;
+        }
+
+        self.data_length = 0;
+        return Some(Self::to_message(status, self.data));
+    }
+
+    pub fn decode(&mut self, bytes: &[u8]) -> Vec<MidiMessage> {
+        return bytes.iter().filter_map(|byte| self.feed(*byte)).collect();
+    }
+
+    fn handle_status(&mut self, status: u8) -> Option<MidiMessage> {
+        self.data_length = 0;
+        self.bytes_to_skip = 0;
+        self.in_sysex = false;
+
+        if status < 0xF0 {
+            self.running_status = Some(status);
+            return None;
+        }
+
+        // System exclusive and system common messages cancel running status
+        self.running_status = None;
+        match status {
+            0xF0 => self.in_sysex = true,
+            0xF1 | 0xF3 => self.bytes_to_skip = 1,
+            0xF2 => self.bytes_to_skip = 2,
+            _ => {},
+        }
+
+        return None;
+    }
+
+    fn to_message(status: u8, data: [u8; 2]) -> MidiMessage {
+        let channel = status & 0x0F;
+
+        return match status & 0xF0 {
+            0x80 => MidiMessage::NoteOff { channel, note: data[0], velocity: data[1] },
+            0x90 => MidiMessage::NoteOn { channel, note: data[0], velocity: data[1] },
+            0xA0 => MidiMessage::PolyPressure { channel, note: data[0], pressure: data[1] },
+            0xB0 => MidiMessage::ControlChange { channel, controller: data[0], value: data[1] },
+            0xC0 => MidiMessage::ProgramChange { channel, program: data[0] },
+            0xD0 => MidiMessage::ChannelPressure { channel, pressure: data[0] },
+            _ => {
+                let value = ((data[1] as i32) << 7 | data[0] as i32) - PITCH_BEND_CENTRE;
+                MidiMessage::PitchBend { channel, value: value as i16 }
+            },
+        };
+    }
+}
+
+// Reads raw MIDI from any byte source (a file, a pipe, a raw MIDI device such as
+// /dev/snd/midiC1D0) and forwards it to the synth until the source ends.
+pub fn send_synth_events<R: Read>(mut reader: R, sender: &Sender<SynthEvent>) -> io::Result<()> {
+    let mut decoder = MidiDecoder::new();
+    let mut buffer = [0u8; 256];
+
+    loop {
+        let bytes_read = match reader.read(&mut buffer) {
+            Ok(0) => return Ok(()),
+            Ok(n) => n,
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+            Err(error) => return Err(error),
+        };
+
+        for message in decoder.decode(&buffer[..bytes_read]) {
+            if let Some(event) = message.to_synth_event() {
+                if sender.send(event).is_err() {
+                    // Synth has gone away, nothing left to feed
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,458 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use rodio::Source;
+
+use crate::constants::*;
+use crate::synthesizer::{Synthesizer, SynthEvent, TimedSynthEvent};
+
+const DEFAULT_MICROS_PER_QUARTER: u32 = 500_000;
+
+const META_END_OF_TRACK: u8 = 0x2F;
+const META_TEMPO: u8 = 0x51;
+const META_TIME_SIGNATURE: u8 = 0x58;
+
+#[derive(Debug)]
+pub enum MidiFileError {
+    Io(std::io::Error),
+    NotAMidiFile,
+    UnexpectedEndOfData(usize),
+    UnsupportedFormat(u16),
+    InvalidTimeDivision(u16),
+    MissingRunningStatus(usize),
+}
+
+impl fmt::Display for MidiFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            MidiFileError::Io(error) => write!(f, "Failed to read MIDI file: {}", error),
+            MidiFileError::NotAMidiFile => write!(f, "Missing MThd header, not a Standard MIDI File"),
+            MidiFileError::UnexpectedEndOfData(offset) => write!(f, "Unexpected end of data at byte {}", offset),
+            MidiFileError::UnsupportedFormat(format) => write!(f, "Unsupported SMF format {}, only type 0 and 1 are supported", format),
+            MidiFileError::InvalidTimeDivision(division) => write!(f, "Invalid time division {:#06x}", division),
+            MidiFileError::MissingRunningStatus(offset) => write!(f, "Data byte without a preceding status byte at byte {}", offset),
+        };
+    }
+}
+
+impl std::error::Error for MidiFileError {}
+
+impl From<std::io::Error> for MidiFileError {
+    fn from(error: std::io::Error) -> Self {
+        return MidiFileError::Io(error);
+    }
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum MidiEventKind {
+    NoteOn { channel: u8, note: u8, velocity: u8 },
+    NoteOff { channel: u8, note: u8, velocity: u8 },
+    Tempo (u32),
+    TimeSignature (TimeSignature),
+    EndOfTrack,
+    Other,
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct MidiTrackEvent {
+    pub tick: u64,
+    pub kind: MidiEventKind,
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct TimeSignature {
+    pub numerator: u8,
+    pub denominator: u8,
+    pub clocks_per_click: u8,
+    pub thirty_seconds_per_quarter: u8,
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum TimeDivision {
+    TicksPerQuarter (u16),
+    // SMPTE timing is absolute and ignores tempo changes
+    Smpte { frames_per_second: u8, ticks_per_frame: u8 },
+}
+
+impl TimeDivision {
+    fn from_header(division: u16) -> Result<TimeDivision, MidiFileError> {
+        if division & 0x8000 == 0 {
+            if division == 0 {
+                return Err(MidiFileError::InvalidTimeDivision(division));
+            }
+
+            return Ok(TimeDivision::TicksPerQuarter(division));
+        }
+
+        // Upper byte is the negated frame rate, 29 means 29.97 drop frame
+        let frames_per_second = ((division >> 8) as u8 as i8).unsigned_abs();
+        let ticks_per_frame = (division & 0xFF) as u8;
+
+        if ![24, 25, 29, 30].contains(&frames_per_second) || ticks_per_frame == 0 {
+            return Err(MidiFileError::InvalidTimeDivision(division));
+        }
+
+        return Ok(TimeDivision::Smpte { frames_per_second, ticks_per_frame });
+    }
+}
+
+pub struct MidiFile {
+    pub format: u16,
+    pub division: TimeDivision,
+    pub tracks: Vec<Vec<MidiTrackEvent>>,
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Reader<'a> {
+        return Reader { bytes, position: 0 };
+    }
+
+    fn is_at_end(&self) -> bool {
+        return self.position >= self.bytes.len();
+    }
+
+    fn read_bytes(&mut self, count: usize) -> Result<&'a [u8], MidiFileError> {
+        if self.position + count > self.bytes.len() {
+            return Err(MidiFileError::UnexpectedEndOfData(self.position));
+        }
+
+        let slice = &self.bytes[self.position..self.position + count];
+        self.position += count;
+        return Ok(slice);
+    }
+
+    fn read_u8(&mut self) -> Result<u8, MidiFileError> {
+        return Ok(self.read_bytes(1)?[0]);
+    }
+
+    fn peek_u8(&self) -> Result<u8, MidiFileError> {
+        return self.bytes.get(self.position).copied().ok_or(MidiFileError::UnexpectedEndOfData(self.position));
+    }
+
+    fn read_u16(&mut self) -> Result<u16, MidiFileError> {
+        let bytes = self.read_bytes(2)?;
+        return Ok(u16::from_be_bytes([bytes[0], bytes[1]]));
+    }
+
+    fn read_u32(&mut self) -> Result<u32, MidiFileError> {
+        let bytes = self.read_bytes(4)?;
+        return Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]));
+    }
+
+    fn read_variable_length(&mut self) -> Result<u32, MidiFileError> {
+        let mut value: u32 = 0;
+
+        // Variable length quantities are at most 4 bytes
+        for _ in 0..4 {
+            let byte = self.read_u8()?;
+            value = (value << 7) | (byte & 0x7F) as u32;
+
+            if byte & 0x80 == 0 {
+                break;
+            }
+        }
+
+        return Ok(value);
+    }
+}
+
+impl MidiFile {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<MidiFile, MidiFileError> {
+        let bytes = fs::read(path)?;
+        return Self::parse(&bytes);
+    }
+
+    pub fn parse(bytes: &[u8]) -> Result<MidiFile, MidiFileError> {
+        let mut reader = Reader::new(bytes);
+
+        if reader.read_bytes(4).map_err(|_| MidiFileError::NotAMidiFile)? != b"MThd" {
+            return Err(MidiFileError::NotAMidiFile);
+        }
+
+        let header_length = reader.read_u32()? as usize;
+        let format = reader.read_u16()?;
+        let num_tracks = reader.read_u16()?;
+        let division = reader.read_u16()?;
+        reader.read_bytes(header_length.saturating_sub(6))?;
+
+        if format > 1 {
+            return Err(MidiFileError::UnsupportedFormat(format));
+        }
+
+        let division = TimeDivision::from_header(division)?;
+
+        let mut tracks: Vec<Vec<MidiTrackEvent>> = Vec::with_capacity(num_tracks as usize);
+        while !reader.is_at_end() && tracks.len() < num_tracks as usize {
+            let chunk_type = reader.read_bytes(4)?;
+            let chunk_length = reader.read_u32()? as usize;
+            let chunk_start = reader.position;
+            reader.read_bytes(chunk_length)?;
+
+            // Unknown chunks must be skipped
+            if chunk_type == b"MTrk" {
+                tracks.push(Self::parse_track(&bytes[..reader.position], chunk_start)?);
+            }
+        }
+
+        return Ok(MidiFile {
+            format,
+            division,
+            tracks,
+        });
+    }
+
+    // Reads the chunk from its start to the end of the bytes, so errors give offsets into the file
+    fn parse_track(bytes: &[u8], chunk_start: usize) -> Result<Vec<MidiTrackEvent>, MidiFileError> {
+        let mut reader = Reader { bytes, position: chunk_start };
+        let mut events: Vec<MidiTrackEvent> = Vec::new();
+
+        let mut tick: u64 = 0;
+        let mut running_status: Option<u8> = None;
+
+        while !reader.is_at_end() {
+            tick += reader.read_variable_length()? as u64;
+
+            let status = if reader.peek_u8()? & 0x80 != 0 {
+                reader.read_u8()?
+            } else {
+                running_status.ok_or(MidiFileError::MissingRunningStatus(reader.position))?
+            };
+
+            let kind = match status {
+                0xFF => {
+                    running_status = None;
+                    let meta_type = reader.read_u8()?;
+                    let length = reader.read_variable_length()? as usize;
+                    let data = reader.read_bytes(length)?;
+
+                    match meta_type {
+                        META_TEMPO if length == 3 => MidiEventKind::Tempo(u32::from_be_bytes([0, data[0], data[1], data[2]])),
+                        META_TIME_SIGNATURE if length == 4 => MidiEventKind::TimeSignature(TimeSignature {
+                            numerator: data[0],
+                            denominator: 1 << data[1].min(7),
+                            clocks_per_click: data[2],
+                            thirty_seconds_per_quarter: data[3],
+                        }),
+                        META_END_OF_TRACK => MidiEventKind::EndOfTrack,
+                        _ => MidiEventKind::Other,
+                    }
+                },
+                0xF0 | 0xF7 => {
+                    running_status = None;
+                    let length = reader.read_variable_length()? as usize;
+                    reader.read_bytes(length)?;
+                    MidiEventKind::Other
+                },
+                _ => {
+                    running_status = Some(status);
+                    let channel = status & 0x0F;
+
+                    match status & 0xF0 {
+                        0x80 => {
+                            let note = reader.read_u8()?;
+                            let velocity = reader.read_u8()?;
+                            MidiEventKind::NoteOff { channel, note, velocity }
+                        },
+                        0x90 => {
+                            let note = reader.read_u8()?;
+                            let velocity = reader.read_u8()?;
+
+                            // Note on with zero velocity is a note off
+                            if velocity == 0 {
+                                MidiEventKind::NoteOff { channel, note, velocity }
+                            } else {
+                                MidiEventKind::NoteOn { channel, note, velocity }
+                            }
+                        },
+                        0xC0 | 0xD0 => {
+                            reader.read_bytes(1)?;
+                            MidiEventKind::Other
+                        },
+                        _ => {
+                            reader.read_bytes(2)?;
+                            MidiEventKind::Other
+                        },
+                    }
+                },
+            };
+
+            if kind != MidiEventKind::Other {
+                events.push(MidiTrackEvent { tick, kind });
+            }
+
+            if kind == MidiEventKind::EndOfTrack {
+                break;
+            }
+        }
+
+        return Ok(events);
+    }
+
+    // Tempo changes can live in any track (type 1 files normally keep them in the first one)
+    // so they are collected into one map that applies to every track.
+    fn get_tempo_map(&self) -> Vec<(u64, u32)> {
+        let mut tempo_map: Vec<(u64, u32)> = self.tracks
+            .iter()
+            .flatten()
+            .filter_map(|event| match event.kind {
+                MidiEventKind::Tempo(micros_per_quarter) => Some((event.tick, micros_per_quarter)),
+                _ => None,
+            })
+            .collect();
+
+        tempo_map.sort_by_key(|(tick, _)| *tick);
+        return tempo_map;
+    }
+
+    // Microseconds since the start of the file, kept in f64 so long files still land on the right sample
+    fn tick_to_micros(&self, tempo_map: &[(u64, u32)], tick: u64) -> f64 {
+        let ticks_per_quarter = match self.division {
+            TimeDivision::TicksPerQuarter(ticks_per_quarter) => ticks_per_quarter as f64,
+            TimeDivision::Smpte { frames_per_second, ticks_per_frame } => {
+                let frames_per_second = if frames_per_second == 29 { 29.97 } else { frames_per_second as f64 };
+                return tick as f64 * 1_000_000.0 / (frames_per_second * ticks_per_frame as f64);
+            },
+        };
+
+        let mut micros = 0.0;
+        let mut last_tick = 0;
+        let mut micros_per_quarter = DEFAULT_MICROS_PER_QUARTER;
+
+        for (tempo_tick, tempo) in tempo_map {
+            if *tempo_tick >= tick {
+                break;
+            }
+
+            micros += (tempo_tick - last_tick) as f64 * micros_per_quarter as f64 / ticks_per_quarter;
+            last_tick = *tempo_tick;
+            micros_per_quarter = *tempo;
+        }
+
+        micros += (tick - last_tick) as f64 * micros_per_quarter as f64 / ticks_per_quarter;
+        return micros;
+    }
+
+    fn tick_to_sample(&self, tempo_map: &[(u64, u32)], tick: u64) -> u64 {
+        return (self.tick_to_micros(tempo_map, tick) * SAMPLE_RATE as f64 / 1_000_000.0).round() as u64;
+    }
+
+    pub fn get_time_signatures(&self) -> Vec<(u64, TimeSignature)> {
+        let mut time_signatures: Vec<(u64, TimeSignature)> = self.tracks
+            .iter()
+            .flatten()
+            .filter_map(|event| match event.kind {
+                MidiEventKind::TimeSignature(time_signature) => Some((event.tick, time_signature)),
+                _ => None,
+            })
+            .collect();
+
+        time_signatures.sort_by_key(|(tick, _)| *tick);
+        return time_signatures;
+    }
+
+    pub fn get_duration(&self) -> f32 {
+        let tempo_map = self.get_tempo_map();
+        let last_tick = self.tracks
+            .iter()
+            .filter_map(|track| track.last())
+            .map(|event| event.tick)
+            .max()
+            .unwrap_or(0);
+
+        return (self.tick_to_micros(&tempo_map, last_tick) / 1_000_000.0) as f32;
+    }
+
+    pub fn to_synth_events(&self) -> Vec<TimedSynthEvent> {
+        let tempo_map = self.get_tempo_map();
+        let mut events: Vec<TimedSynthEvent> = Vec::new();
+
+        for track in &self.tracks {
+            for event in track {
+                let synth_event = match event.kind {
+                    MidiEventKind::NoteOn { note, velocity, .. } => SynthEvent::NotePress(note as i32, velocity as f32 / 127.0),
+                    MidiEventKind::NoteOff { note, .. } => SynthEvent::NoteRelease(note as i32),
+                    _ => continue,
+                };
+
+                events.push(TimedSynthEvent::at_sample(self.tick_to_sample(&tempo_map, event.tick), synth_event));
+            }
+        }
+
+        events.sort_by_key(|e| e.sample_offset);
+        return events;
+    }
+}
+
+// Plays a MIDI file through a synth in real time. Events are applied by counting
+// rendered samples, so playback is as sample accurate as an offline render while the
+// synth keeps responding to live input.
+pub struct MidiFilePlayer {
+    synth: Synthesizer,
+    events: VecDeque<TimedSynthEvent>,
+    sample_index: u64,
+    pending_right: Option<f32>,
+}
+
+impl MidiFilePlayer {
+    pub fn new(midi_file: &MidiFile, synth: Synthesizer) -> MidiFilePlayer {
+        return MidiFilePlayer {
+            synth,
+            events: midi_file.to_synth_events().into(),
+            sample_index: 0,
+            pending_right: None,
+        };
+    }
+
+    pub fn is_finished(&self) -> bool {
+        return self.events.is_empty();
+    }
+}
+
+impl Iterator for MidiFilePlayer {
+    type Item = f32;
+
+    // Interleaves the synth's frames, events are only applied between frames
+    fn next(&mut self) -> Option<f32> {
+        if let Some(right) = self.pending_right.take() {
+            return Some(right);
+        }
+
+        while self.events.front().is_some_and(|e| e.sample_offset <= self.sample_index) {
+            if let Some(timed_event) = self.events.pop_front() {
+                self.synth.handle_event(timed_event.event);
+            }
+        }
+
+        self.sample_index += 1;
+
+        let [left, right] = self.synth.get_synth_frame();
+        self.pending_right = Some(right);
+        return Some(left);
+    }
+}
+
+impl Source for MidiFilePlayer {
+    fn channels(&self) -> u16 {
+        return NUM_CHANNELS;
+    }
+
+    fn sample_rate(&self) -> u32 {
+        return SAMPLE_RATE as u32;
+    }
+
+    fn current_frame_len(&self) -> Option<usize> {
+        return None;
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        return None;
+    }
+}
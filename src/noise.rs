@@ -0,0 +1,45 @@
+use crate::random::Random;
+
+// White, pink and brown noise from a seeded Random, so the same seed always gives the same
+// noise and offline renders stay reproducible
+#[derive(Clone)]
+pub struct NoiseGenerator {
+    random: Random,
+    pink_state: [f32; 3],
+    brown_state: f32,
+}
+
+impl NoiseGenerator {
+    pub fn new(seed: u32) -> NoiseGenerator {
+        return NoiseGenerator {
+            random: Random::new(seed),
+            pink_state: [0.0; 3],
+            brown_state: 0.0,
+        };
+    }
+
+    // Flat spectrum from -1.0 to 1.0
+    pub fn white(&mut self) -> f32 {
+        return self.random.next_bipolar();
+    }
+
+    // -3dB per octave using Paul Kellet's economy filter, scaled to roughly the level of white
+    pub fn pink(&mut self) -> f32 {
+        let white = self.white();
+
+        let b = &mut self.pink_state;
+        b[0] = 0.99765 * b[0] + white * 0.0990460;
+        b[1] = 0.96300 * b[1] + white * 0.2965164;
+        b[2] = 0.57000 * b[2] + white * 1.0526913;
+
+        return (b[0] + b[1] + b[2] + white * 0.1848) * 0.25;
+    }
+
+    // -6dB per octave from leaky integrated white noise
+    pub fn brown(&mut self) -> f32 {
+        let white = self.white();
+        self.brown_state = (self.brown_state + 0.02 * white) / 1.02;
+
+        return self.brown_state * 3.5;
+    }
+}
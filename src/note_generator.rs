@@ -1,45 +1,115 @@
+use std::f32::consts::PI;
+use std::sync::Arc;
+
 use strum::EnumCount;
 
-use crate::constants::OscNumber;
-use crate::time;
+use crate::constants::{OscNumber, SoundGenOscParams, SubOscParams, FilterParams, ModEnvelopeNumber, ModEnvelopeParams, VelocityParams, SAMPLE_RATE, MIN_PULSE_WIDTH, MAX_PULSE_WIDTH};
+use crate::envelope::{EnvelopeADSR, VoiceEnvelope};
+use crate::filter::{StateVariableFilter, LadderFilter};
 use crate::oscillator::Oscillator;
-use crate::wavetype::WaveType;
+use crate::random::Random;
+use crate::time::SampleClock;
+use crate::tuning::Tuning;
+use crate::wavetables::WaveTable;
+use crate::wavetype::{WaveType, OscillatorQuality, PhaseMode, OscModulation, FmAlgorithm, FilterMode, EnvelopeDestination};
 
 
 const UNISON_MAX_NOTE_DETUNE: f32 = 2.0;
+const PHASE_SEED: u32 = 0x0F5E_7A11;
 
+#[derive(Clone)]
 pub struct NoteOscillatorParams {
     wave_type: WaveType,
+    quality: OscillatorQuality,
+    pulse_width: f32,
+    pwm_depth: f32,
     unisons: i32,
-    unison_detune_pct: f32
+    unison_detune_pct: f32,
+    unison_spread: f32,
+    phase_mode: PhaseMode,
+    start_phase: f32,
+    modulation: OscModulation,
+    modulator: usize,
+    wavetable: Option<Arc<WaveTable>>,
+    wavetable_position: f32,
+    gain: f32,
+    pan: f32,
+    // Octave, semitone and fine tune offsets together in semitones
+    pitch_offset: f32,
 }
 
 impl NoteOscillatorParams {
-    pub fn new(wave_type: WaveType, unisons: i32, unison_detune_pct: f32) -> NoteOscillatorParams {
+    pub fn new(osc_params: &SoundGenOscParams) -> NoteOscillatorParams {
         return NoteOscillatorParams{
-            wave_type, unisons, unison_detune_pct
+            wave_type: osc_params.wave_type,
+            quality: osc_params.quality,
+            pulse_width: osc_params.pulse_width,
+            pwm_depth: osc_params.pwm_depth,
+            unisons: osc_params.unisons,
+            unison_detune_pct: osc_params.unison_detune_pct,
+            unison_spread: osc_params.unison_spread,
+            phase_mode: osc_params.phase_mode,
+            start_phase: osc_params.start_phase,
+            modulation: osc_params.modulation,
+            modulator: osc_params.modulator as usize,
+            wavetable: osc_params.wavetable.clone(),
+            wavetable_position: osc_params.wavetable_position,
+            gain: osc_params.gain,
+            pan: osc_params.pan,
+            pitch_offset: osc_params.get_pitch_offset(),
         };
     }
 }
 
+// How the press was played and the synth settings a new note starts from
+pub struct NotePress<'a> {
+    pub velocity: f32,
+    pub velocity_params: &'a VelocityParams,
+    pub mod_envelopes: &'a [ModEnvelopeParams; ModEnvelopeNumber::COUNT],
+    pub tuning: &'a Tuning,
+}
+
 pub struct NoteGenerator {
-    pub trigger_on_time: f32,
-    pub trigger_off_time: f32,
+    // Sample counts from the synth's clock when the note was pressed and released
+    pub trigger_on_sample: u64,
+    pub trigger_off_sample: u64,
     pub note_pressed: bool,
+    pub velocity_amplitude: f32,
+    pub attack_scale: f32,
     note: i32,
+    velocity: f32,
+    pitch_offset: f32,
     
-    oscillators: [Option<Vec<Oscillator>>; OscNumber::COUNT]
+    oscillators: [Option<Vec<Oscillator>>; OscNumber::COUNT],
+    note_params: [Option<NoteOscillatorParams>; OscNumber::COUNT],
+    // Start phases in the random phase mode
+    random: Random,
+    // Each oscillator's last output before its gain, for ring and amplitude modulation
+    osc_values: [f32; OscNumber::COUNT],
+
+    // Single voice below the note, it isn't part of unison, modulation or FM
+    sub_oscillator: Option<Oscillator>,
+    sub_octave: i32,
+
+    filter: StateVariableFilter,
+    ladder: LadderFilter,
+    // Filter and mod envelopes, timed from this note's press and release
+    envelopes: [VoiceEnvelope; ModEnvelopeNumber::COUNT],
 }
 
 impl NoteGenerator {
-    pub fn new(note: i32, note_params: [Option<NoteOscillatorParams>; OscNumber::COUNT]) -> NoteGenerator {
+    pub fn new(note: i32, press: &NotePress, note_params: [Option<NoteOscillatorParams>; OscNumber::COUNT], pitch_offset: f32, sample: u64) -> NoteGenerator {
         const INIT: Option<Vec<Oscillator>> = None;
         let mut oscillators: [Option<Vec<Oscillator>>; OscNumber::COUNT] = [INIT; OscNumber::COUNT];
+        // Seeded from the note and sample so renders are reproducible but each press sounds different
+        let mut random = Random::new(PHASE_SEED ^ (note as u32).wrapping_mul(0x9E37_79B9) ^ sample as u32 ^ (sample >> 32) as u32);
 
         for (osc_num, opt) in note_params.iter().enumerate() { 
             match opt {
                 Some(param) => {
-                    let osc_unison_voices = Self::get_unison_voices_for_note(note, param, 0.0);
+                    let mut osc_unison_voices = Self::get_unison_voices_for_note(Self::get_pitched_frequency(note, pitch_offset, press.tuning), param, param.start_phase);
+                    Self::set_start_phases(&mut osc_unison_voices, param.phase_mode, &mut random, sample);
+                    Self::set_noise_seeds(&mut osc_unison_voices, &mut random);
                     oscillators[osc_num] = Some(osc_unison_voices);
                 },
                 None => oscillators[osc_num] = None,
@@ -47,68 +117,197 @@ impl NoteGenerator {
         }
 
         return NoteGenerator{
-            trigger_on_time: time::get_time(),
-            trigger_off_time: 0.0,
+            trigger_on_sample: sample,
+            trigger_off_sample: 0,
             note_pressed: true,
+            velocity_amplitude: press.velocity_params.get_amplitude(press.velocity),
+            attack_scale: press.velocity_params.get_attack_scale(press.velocity),
             note,
+            velocity: press.velocity,
+            pitch_offset,
             oscillators: oscillators,
+            note_params,
+            random,
+            osc_values: [0.0; OscNumber::COUNT],
+            sub_oscillator: None,
+            sub_octave: -1,
+            filter: StateVariableFilter::new(FilterMode::Off),
+            ladder: LadderFilter::new(),
+            envelopes: press.mod_envelopes.each_ref().map(VoiceEnvelope::new),
         };
     }
 
-    fn get_unison_voices_for_note(note: i32, param: &NoteOscillatorParams, starting_sample_index: f32) -> Vec<Oscillator> {
+    pub fn set_sub_osc_params(&mut self, sub_osc_params: &SubOscParams, tuning: &Tuning) {
+        if !sub_osc_params.enabled {
+            self.sub_oscillator = None;
+            return;
+        }
+
+        self.sub_octave = sub_osc_params.octave.clamp(-2, -1);
+        let freq = self.get_sub_frequency(tuning);
+
+        match &mut self.sub_oscillator {
+            Some(osc) => {
+                osc.set_frequency(freq);
+                osc.set_wave_type(sub_osc_params.wave_type);
+            },
+            None => {
+                let mut osc = Oscillator::new(freq, sub_osc_params.wave_type, 0.0);
+                osc.set_noise_seed(self.random.next_u32());
+                self.sub_oscillator = Some(osc);
+            },
+        }
+
+        if let Some(osc) = &mut self.sub_oscillator {
+            osc.set_gain(sub_osc_params.gain);
+        }
+    }
+
+    fn get_sub_frequency(&self, tuning: &Tuning) -> f32 {
+        return Self::detune(Self::get_pitched_frequency(self.note, self.pitch_offset, tuning), (self.sub_octave * 12) as f32);
+    }
+
+    // Fixed mode voices keep the phase they were created with
+    fn set_start_phases(osc_unison_voices: &mut [Oscillator], phase_mode: PhaseMode, random: &mut Random, sample: u64) {
+        for osc in osc_unison_voices {
+            let start_phase = match phase_mode {
+                PhaseMode::Fixed => continue,
+                PhaseMode::Random => random.next_f32(),
+                // Cycles since the synth started, from the sample count so long sessions don't lose the phase
+                PhaseMode::FreeRunning => (osc.get_frequency() as f64 * sample as f64 / SAMPLE_RATE as f64).fract() as f32,
+            };
+
+            osc.set_start_phase(start_phase);
+            osc.reset_phase();
+        }
+    }
+
+    // Every voice plays noise of its own, even on the same note with no detune
+    fn set_noise_seeds(osc_unison_voices: &mut [Oscillator], random: &mut Random) {
+        for osc in osc_unison_voices {
+            osc.set_noise_seed(random.next_u32());
+        }
+    }
+
+    fn get_unison_voices_for_note(freq: f32, param: &NoteOscillatorParams, start_phase: f32) -> Vec<Oscillator> {
+        let freq = Self::detune(freq, param.pitch_offset);
         let mut osc_unison_voices: Vec<Oscillator> = Vec::new(); //todo: remove allocations
 
         if param.unisons % 2 == 0 {
             let unisons_to_add = param.unisons / 2;
-            Self::add_unison_oscillators_to_vec(&mut osc_unison_voices, note, unisons_to_add, param.unison_detune_pct, param.wave_type, starting_sample_index);
+            Self::add_unison_oscillators_to_vec(&mut osc_unison_voices, freq, unisons_to_add, param, start_phase);
 
         } else {
             if param.unisons > 1 {
                 let unisons_to_add = (param.unisons - 1) / 2;
-                Self::add_unison_oscillators_to_vec(&mut osc_unison_voices, note, unisons_to_add, param.unison_detune_pct, param.wave_type, starting_sample_index);
+                Self::add_unison_oscillators_to_vec(&mut osc_unison_voices, freq, unisons_to_add, param, start_phase);
             }
 
-            let freq = Self::get_frequency(note as f32);
-            osc_unison_voices.push(Oscillator::new(freq, param.wave_type, starting_sample_index));
+            osc_unison_voices.push(Self::create_oscillator(freq, param.pan, param, start_phase));
         }
 
         return osc_unison_voices;
     }
 
     
-    fn add_unison_oscillators_to_vec(osc_vec: &mut Vec<Oscillator>, note: i32, unisons_to_add: i32, detune_pct: f32, wave_type: WaveType, starting_sample_index: f32){
+    fn add_unison_oscillators_to_vec(osc_vec: &mut Vec<Oscillator>, freq: f32, unisons_to_add: i32, param: &NoteOscillatorParams, start_phase: f32){
         for i in 0..unisons_to_add {
-            let note_detune = detune_pct * UNISON_MAX_NOTE_DETUNE / (2.0 as f32).powi(i);
+            let note_detune = param.unison_detune_pct * UNISON_MAX_NOTE_DETUNE / (2.0 as f32).powi(i);
+            let spread = Self::get_unison_spread(param, i);
 
-            let above = Self::get_frequency(note as f32 + note_detune);
-            osc_vec.push(Oscillator::new(above, wave_type, starting_sample_index));
+            let above = Self::detune(freq, note_detune);
+            osc_vec.push(Self::create_oscillator(above, param.pan + spread, param, start_phase));
 
-            let below = Self::get_frequency(note as f32 - note_detune);
-            osc_vec.push(Oscillator::new(below, wave_type, starting_sample_index));
+            let below = Self::detune(freq, -note_detune);
+            osc_vec.push(Self::create_oscillator(below, param.pan - spread, param, start_phase));
         }
     }
 
-    fn set_unison_params(osc_unison_voices: &mut Vec<Oscillator>, note: i32, num_unisons: usize, note_params: &NoteOscillatorParams) {
+    // Pairs are spread like they are detuned, the outermost pair at the full width and each
+    // pair after at half the width of the last
+    fn get_unison_spread(param: &NoteOscillatorParams, pair: i32) -> f32 {
+        return param.unison_spread / (2.0 as f32).powi(pair);
+    }
+
+    fn create_oscillator(freq: f32, pan: f32, param: &NoteOscillatorParams, start_phase: f32) -> Oscillator {
+        let mut osc = Oscillator::new(freq, param.wave_type, start_phase);
+        osc.set_quality(param.quality);
+        osc.set_gain(param.gain);
+        osc.set_pan(pan);
+        osc.set_wavetable(param.wavetable.clone());
+        osc.set_wavetable_position(param.wavetable_position);
+
+        return osc;
+    }
+
+    fn apply_params(osc: &mut Oscillator, freq: f32, pan: f32, param: &NoteOscillatorParams) {
+        osc.set_frequency(freq);
+        osc.set_wave_type(param.wave_type);
+        osc.set_quality(param.quality);
+        osc.set_gain(param.gain);
+        osc.set_pan(pan);
+        osc.set_wavetable(param.wavetable.clone());
+        osc.set_wavetable_position(param.wavetable_position);
+    }
+
+    // Voices are laid out as detuned above/below pairs with the centre voice last for odd counts
+    fn set_unison_params(osc_unison_voices: &mut [Oscillator], freq: f32, note_params: &NoteOscillatorParams) {
+        let freq = Self::detune(freq, note_params.pitch_offset);
+        let num_unisons = osc_unison_voices.len() / 2;
+
         for i in 0..num_unisons {
             let note_detune = note_params.unison_detune_pct * UNISON_MAX_NOTE_DETUNE / (2.0 as f32).powi(i as i32);
+            let spread = Self::get_unison_spread(note_params, i as i32);
 
-            let above = Self::get_frequency(note as f32 + note_detune);
-            osc_unison_voices[i * 2].set_frequency(above);
-            osc_unison_voices[i * 2].set_wave_type(note_params.wave_type);
+            let above = Self::detune(freq, note_detune);
+            Self::apply_params(&mut osc_unison_voices[i * 2], above, note_params.pan + spread, note_params);
 
-            let below = Self::get_frequency(note as f32 - note_detune);
-            osc_unison_voices[(i * 2) + 1].set_frequency(below);
-            osc_unison_voices[(i * 2) + 1].set_wave_type(note_params.wave_type);
+            let below = Self::detune(freq, -note_detune);
+            Self::apply_params(&mut osc_unison_voices[(i * 2) + 1], below, note_params.pan - spread, note_params);
+        }
+
+        if osc_unison_voices.len() % 2 == 1 {
+            if let Some(centre) = osc_unison_voices.last_mut() {
+                Self::apply_params(centre, freq, note_params.pan, note_params);
+            }
         }
     }
 
-    pub fn set_note_params(&mut self, osc_num: usize, note_params: &NoteOscillatorParams){
+    // Detune and pitch bend are in equal tempered semitones relative to the tuned pitch, so
+    // they behave the same with any scale loaded
+    fn detune(freq: f32, semitones: f32) -> f32 {
+        return freq * (semitones / 12.0).exp2();
+    }
+
+    fn get_pitched_frequency(note: i32, pitch_offset: f32, tuning: &Tuning) -> f32 {
+        return Self::detune(tuning.get_frequency(note), pitch_offset);
+    }
 
-        let mut starting_sample_index = 0.0;
+    // Retunes every voice in place so pitch bend and tuning changes apply to held notes
+    // without restarting them
+    pub fn retune(&mut self, pitch_offset: f32, tuning: &Tuning) {
+        self.pitch_offset = pitch_offset;
+        let freq = Self::get_pitched_frequency(self.note, pitch_offset, tuning);
+
+        for (opt, params) in self.oscillators.iter_mut().zip(self.note_params.iter()) {
+            if let (Some(osc_unison_voices), Some(note_params)) = (opt, params) {
+                Self::set_unison_params(osc_unison_voices, freq, note_params);
+            }
+        }
+
+        let sub_freq = self.get_sub_frequency(tuning);
+        if let Some(osc) = &mut self.sub_oscillator {
+            osc.set_frequency(sub_freq);
+        }
+    }
+
+    pub fn set_note_params(&mut self, osc_num: usize, note_params: &NoteOscillatorParams, tuning: &Tuning, sample: u64){
+
+        let mut start_phase = note_params.start_phase;
         let mut current_unison_voice_count = 0;
         if let Some(osc_unison_voices) = &mut self.oscillators[osc_num] {
             if let Some(voice) = osc_unison_voices.first() {
-                starting_sample_index = voice.get_sample_index();
+                start_phase = voice.get_phase();
             }
 
             current_unison_voice_count = osc_unison_voices.len();
@@ -118,51 +317,178 @@ impl NoteGenerator {
         //unisons start from the outside in 2/1/0.5/0.25/etc...
         //if we removed unisons only need to remove the inner voices
         //if we added unisons only need to add the inner voices
+        let freq = Self::get_pitched_frequency(self.note, self.pitch_offset, tuning);
         if note_params.unisons as usize != current_unison_voice_count {
-            let unison_voices = Self::get_unison_voices_for_note(self.note, note_params, starting_sample_index);
+            let mut unison_voices = Self::get_unison_voices_for_note(freq, note_params, start_phase);
+            Self::set_start_phases(&mut unison_voices, note_params.phase_mode, &mut self.random, sample);
+            Self::set_noise_seeds(&mut unison_voices, &mut self.random);
             self.oscillators[osc_num] = Some(unison_voices);
         }
         else {
             if let Some(osc_unison_voices) = &mut self.oscillators[osc_num] {
-                if current_unison_voice_count % 2 == 0 {
-                    let num_unisons = current_unison_voice_count / 2;
-                    Self::set_unison_params(osc_unison_voices, self.note, num_unisons, note_params);
-
-                } else {
-                    let num_unisons = (current_unison_voice_count - 1) / 2;
-                    Self::set_unison_params(osc_unison_voices, self.note, num_unisons, note_params);
-                }
+                Self::set_unison_params(osc_unison_voices, freq, note_params);
             }
         }
-    }
 
-    fn get_frequency(i: f32) -> f32{
-        let base_frequency = 220.0;
-        let twelfth_root_of_two = (2.0 as f32).powf(1.0 / 12.0);
-        return base_frequency * twelfth_root_of_two.powf(i as f32);
+        self.note_params[osc_num] = Some(note_params.clone());
     }
 
-    pub fn note_released(&mut self){
-        self.trigger_off_time = time::get_time();
+    pub fn note_released(&mut self, sample: u64){
+        self.trigger_off_sample = sample;
         self.note_pressed = false;
     }
 
-    pub fn get_sample(&mut self, lfo_freq: f32, lfo_amplitude: f32) -> f32 {
-        let mut total = 0.0;
+    pub fn set_mod_envelope_params(&mut self, mod_envelope_params: &ModEnvelopeParams){
+        self.envelopes[mod_envelope_params.num as usize].set_params(mod_envelope_params);
+    }
+
+    // Seconds since the press and how long the note was held before its release. Only the
+    // differences are converted, so they keep sample precision however long the synth runs.
+    fn get_envelope_times(&self, sample: u64) -> (f32, f32) {
+        let lifetime = SampleClock::samples_to_time(sample.saturating_sub(self.trigger_on_sample));
+        let held_time = SampleClock::samples_to_time(self.trigger_off_sample.saturating_sub(self.trigger_on_sample));
+
+        return (lifetime, held_time);
+    }
+
+    // Level of an envelope that follows this note's press and release
+    pub fn get_envelope_level(&self, envelope: &EnvelopeADSR, sample: u64, attack_scale: f32) -> f32 {
+        let (lifetime, held_time) = self.get_envelope_times(sample);
+        return envelope.get_amplitude(lifetime, 0.0, held_time, self.note_pressed, attack_scale);
+    }
+
+    // Moves the filter and mod envelopes on to `sample`, once before each frame
+    pub fn update_envelopes(&mut self, sample: u64){
+        let (lifetime, held_time) = self.get_envelope_times(sample);
+
+        for envelope in &mut self.envelopes {
+            envelope.update(lifetime, 0.0, held_time, self.note_pressed);
+        }
+    }
+
+    // Every envelope sent to the destination added together
+    fn get_envelope_modulation(&self, destination: EnvelopeDestination) -> f32 {
+        return self.envelopes.iter().map(|envelope| envelope.get_modulation(destination)).sum();
+    }
+
+    // Left and right samples with every voice panned into place. Oscillators run in order, so
+    // one modulated by a later oscillator follows it a sample behind. FM algorithms run their
+    // modulators first, scaled by the note's current FM index.
+    pub fn get_frame(&mut self, lfo_freq: f32, lfo_amplitude: f32, lfo_value: f32, fm_algorithm: FmAlgorithm, fm_index: f32) -> [f32; 2] {
+        let mut total = [0.0, 0.0];
+        // Each oscillator's output after its gain this frame, so gain sets an FM operator's level
+        let mut fm_outputs = [0.0; OscNumber::COUNT];
+        let order: [usize; OscNumber::COUNT] = match fm_algorithm {
+            FmAlgorithm::Off => [0, 1, 2],
+            _ => [2, 1, 0],
+        };
+        let fm_index = fm_index + self.get_envelope_modulation(EnvelopeDestination::FmIndex);
+        let pulse_width_modulation = self.get_envelope_modulation(EnvelopeDestination::PulseWidth);
+        let pitch_modulation = (self.get_envelope_modulation(EnvelopeDestination::Pitch) / 12.0).exp2();
         
-        for opt in &mut self.oscillators {
-            match opt {
-                Some(osc_vec) => {
-                    let num_voices = osc_vec.len() as f32;
-                    for osc in osc_vec {
-                        total += osc.get_sample(lfo_freq, lfo_amplitude) / num_voices;
+        for osc_num in order {
+            let (modulation, modulator, pulse_width) = match &self.note_params[osc_num] {
+                Some(params) => (
+                    self.get_modulation(osc_num, params),
+                    params.modulator,
+                    (params.pulse_width + params.pwm_depth * lfo_value + pulse_width_modulation).clamp(MIN_PULSE_WIDTH, MAX_PULSE_WIDTH),
+                ),
+                None => continue,
+            };
+
+            let modulator_value = self.osc_values[modulator];
+            let phase_modulation = fm_index * fm_algorithm.get_modulators(osc_num).iter().map(|m| fm_outputs[*m]).sum::<f32>() / (2.0 * PI);
+            let is_carrier = fm_algorithm.is_carrier(osc_num);
+            let mut value = 0.0;
+
+            if let Some(osc_vec) = &mut self.oscillators[osc_num] {
+                let num_voices = osc_vec.len() as f32;
+                for osc in osc_vec {
+                    osc.set_pulse_width(pulse_width);
+                    osc.set_phase_modulation(phase_modulation);
+                    osc.set_pitch_modulation(pitch_modulation);
+                    let raw_sample = osc.get_raw_sample(lfo_freq, lfo_amplitude) / num_voices;
+                    value += raw_sample;
+                    let sample = raw_sample * osc.get_amplitude();
+
+                    let sample = match modulation {
+                        OscModulation::RingMod => sample * modulator_value,
+                        OscModulation::AmplitudeMod => sample * (1.0 + modulator_value) * 0.5,
+                        OscModulation::Off | OscModulation::HardSync => sample,
+                    };
+
+                    fm_outputs[osc_num] += sample;
+                    if !is_carrier {
+                        continue;
                     }
-                },
-                None => {},
-            } 
+
+                    let [left, right] = osc.get_pan_gains();
+                    total[0] += sample * left;
+                    total[1] += sample * right;
+                }
+            }
+
+            self.osc_values[osc_num] = value;
+
+            if modulation == OscModulation::HardSync {
+                self.hard_sync(osc_num, modulator);
+            }
+        }
+
+        if let Some(osc) = &mut self.sub_oscillator {
+            osc.set_pitch_modulation(pitch_modulation);
+            let sample = osc.get_sample(lfo_freq, lfo_amplitude);
+            let [left, right] = osc.get_pan_gains();
+            total[0] += sample * left;
+            total[1] += sample * right;
         }
 
         return total;
     }
+
+    pub fn apply_filter(&mut self, frame: [f32; 2], filter_params: &FilterParams) -> [f32; 2] {
+        if filter_params.mode == FilterMode::Off {
+            return frame;
+        }
+
+        let cutoff = filter_params.get_cutoff(self.note, self.velocity, self.get_envelope_modulation(EnvelopeDestination::FilterCutoff));
+
+        if filter_params.mode == FilterMode::Ladder {
+            self.ladder.set_drive(filter_params.drive);
+            self.ladder.set_cutoff(cutoff, filter_params.resonance);
+            return self.ladder.process_frame(frame);
+        }
+
+        self.filter.set_mode(filter_params.mode);
+        self.filter.set_cutoff(cutoff, filter_params.resonance);
+
+        return self.filter.process_frame(frame);
+    }
+
+    // Oscillators can't modulate themselves and disabled ones have nothing to modulate with
+    fn get_modulation(&self, osc_num: usize, params: &NoteOscillatorParams) -> OscModulation {
+        if params.modulator == osc_num || self.note_params[params.modulator].is_none() {
+            return OscModulation::Off;
+        }
+
+        return params.modulation;
+    }
+
+    // Each unison voice follows the modulator's voice in the same position, wrapping round when
+    // the modulator has fewer voices
+    fn hard_sync(&mut self, osc_num: usize, modulator: usize) {
+        let num_voices = self.oscillators[osc_num].as_ref().map_or(0, |voices| voices.len());
+
+        for i in 0..num_voices {
+            let offset = match &self.oscillators[modulator] {
+                Some(modulator_voices) if !modulator_voices.is_empty() => modulator_voices[i % modulator_voices.len()].get_sync_offset(),
+                _ => None,
+            };
+
+            if let (Some(offset), Some(osc_vec)) = (offset, &mut self.oscillators[osc_num]) {
+                osc_vec[i].sync(offset);
+            }
+        }
+    }
     
 }
\ No newline at end of file
@@ -1,33 +1,108 @@
 use std::f32::consts::PI;
+use std::sync::Arc;
 
 use crate::constants::*;
-use crate::wavetype::WaveType;
+use crate::noise::NoiseGenerator;
+use crate::wavetables::{WaveTable, WAVE_TABLES};
+use crate::wavetype::{WaveType, OscillatorQuality};
+
+const NOISE_SEED: u32 = 0x0A15_E5ED;
 
 pub struct Oscillator{
     gain: f32,
     amplitude: f32,
+    // Left and right gains for the pan position
+    pan_gains: [f32; 2],
     frequency: f32,
     wave_type: WaveType,
+    quality: OscillatorQuality,
+    pulse_width: f32,
+
+    // Position in the current cycle from 0.0 to 1.0, advanced by the frequency every sample
+    // so frequency changes never jump the phase
+    phase: f32,
+    start_phase: f32,
+    // Offset in cycles added to the phase when reading the wave form, for FM
+    phase_modulation: f32,
+    // Ratio the frequency is multiplied by, for pitch envelopes
+    pitch_modulation: f32,
+    // Phase advanced by the last sample and whether it wrapped into a new cycle, for hard sync
+    phase_increment: f32,
+    wrapped: bool,
+    // Position in the vibrato cycle, kept per oscillator so the vibrato is continuous too
+    vibrato_phase: f32,
+
+    // A loaded wavetable replaces the wave type
+    wavetable: Option<Arc<WaveTable>>,
+    wavetable_position: f32,
 
-    sample_index: f32,
+    noise: NoiseGenerator,
 }
 
 impl Oscillator {
-    pub fn new(frequency: f32, wave_type: WaveType, starting_sample_index: f32) -> Oscillator {
+    // Start phase is from 0.0 to 1.0 of a cycle
+    pub fn new(frequency: f32, wave_type: WaveType, start_phase: f32) -> Oscillator {
         let gain = 0.0;
 
         return Oscillator { 
             gain: gain,
             frequency, 
             amplitude: Self::calculate_amplitude(gain),
-            sample_index: starting_sample_index,
+            pan_gains: Self::calculate_pan_gains(0.0),
+            phase: start_phase.rem_euclid(1.0),
+            start_phase: start_phase.rem_euclid(1.0),
+            phase_modulation: 0.0,
+            pitch_modulation: 1.0,
+            phase_increment: 0.0,
+            wrapped: false,
+            vibrato_phase: 0.0,
             wave_type: wave_type, //TODO could I make a reference to the value on Synth?? Lifetime questions...
-
+            quality: OscillatorQuality::default(),
+            pulse_width: DEFAULT_PULSE_WIDTH,
+            wavetable: None,
+            wavetable_position: 0.0,
+            // Notes give each voice a seed of its own with set_noise_seed
+            noise: NoiseGenerator::new(NOISE_SEED),
         };
     }
 
-    pub fn get_sample_index(&self) -> f32 {
-        return self.sample_index;
+    pub fn get_phase(&self) -> f32 {
+        return self.phase;
+    }
+
+    // Phase the oscillator goes back to on reset_phase
+    pub fn set_start_phase(&mut self, start_phase: f32) {
+        self.start_phase = start_phase.rem_euclid(1.0);
+    }
+
+    pub fn reset_phase(&mut self) {
+        self.phase = self.start_phase;
+        self.vibrato_phase = 0.0;
+    }
+
+    // Phase modulation in cycles, only moves where the wave form is read so the pitch is unchanged
+    pub fn set_phase_modulation(&mut self, phase_modulation: f32) {
+        self.phase_modulation = phase_modulation;
+    }
+
+    // Frequency ratio, 2.0 plays an octave up without changing the oscillator's own frequency
+    pub fn set_pitch_modulation(&mut self, pitch_modulation: f32) {
+        self.pitch_modulation = pitch_modulation;
+    }
+
+    // Samples since the phase wrapped into a new cycle, None unless it wrapped on the last sample
+    pub fn get_sync_offset(&self) -> Option<f32> {
+        if !self.wrapped || self.phase_increment <= 0.0 {
+            return None;
+        }
+
+        return Some(self.phase / self.phase_increment);
+    }
+
+    // Hard sync, restarts the cycle as if it began `offset` samples ago. The reset isn't band
+    // limited so synced wave forms alias more than free ones.
+    pub fn sync(&mut self, offset: f32) {
+        self.phase = (self.start_phase + offset * self.phase_increment).rem_euclid(1.0);
     }
 
     pub fn get_frequency(&self) -> f32 {
@@ -51,71 +126,185 @@ impl Oscillator {
         self.amplitude = Self::calculate_amplitude(gain);
     }
 
+    // Equal power pan law, -1.0 is hard left and 1.0 hard right with both sides 3dB down
+    // in the centre
+    fn calculate_pan_gains(pan: f32) -> [f32; 2] {
+        let angle = (pan.clamp(-1.0, 1.0) + 1.0) * PI / 4.0;
+        return [angle.cos(), angle.sin()];
+    }
+
+    pub fn set_pan(&mut self, pan: f32){
+        self.pan_gains = Self::calculate_pan_gains(pan);
+    }
+
+    pub fn get_pan_gains(&self) -> [f32; 2] {
+        return self.pan_gains;
+    }
+
     pub fn set_wave_type(&mut self, wave_type: WaveType){
         self.wave_type = wave_type;
     }
 
-    fn t(&self) -> f32 {
-        self.sample_index / SAMPLE_RATE
+    pub fn set_quality(&mut self, quality: OscillatorQuality){
+        self.quality = quality;
+    }
+
+    pub fn set_pulse_width(&mut self, pulse_width: f32){
+        self.pulse_width = pulse_width.clamp(MIN_PULSE_WIDTH, MAX_PULSE_WIDTH);
     }
 
-    fn w(&self, frequency: f32) -> f32 {
-        frequency * 2.0 * PI
+    // Restarts the noise wave types from a seed, the same seed always gives the same noise
+    pub fn set_noise_seed(&mut self, seed: u32){
+        self.noise = NoiseGenerator::new(seed);
     }
 
-    fn get_sin_value(&self, frequency: f32) -> f32 {
-        frequency.sin()
+    pub fn set_wavetable(&mut self, wavetable: Option<Arc<WaveTable>>){
+        self.wavetable = wavetable;
     }
 
-    fn get_saw_value(&self, frequency: f32) -> f32 {
-        let freq_in_hz = frequency / (2.0 * PI * self.t());
+    // 0.0 to 1.0 from the first frame of the wavetable to the last
+    pub fn set_wavetable_position(&mut self, position: f32){
+        self.wavetable_position = position;
+    }
+
+    fn get_sin_value(&self, phase: f32) -> f32 {
+        (phase * 2.0 * PI).sin()
+    }
 
-        (2.0 / PI) * (freq_in_hz * PI * (self.t() % (1.0 / freq_in_hz)) - (PI / 2.0))
+    fn get_saw_value(&self, phase: f32) -> f32 {
+        2.0 * phase - 1.0
     }
 
-    fn get_tri_value(&self, frequency: f32) -> f32 {
-        self.get_sin_value(frequency).asin() * (2.0 / PI)
+    fn get_tri_value(&self, phase: f32) -> f32 {
+        self.get_sin_value(phase).asin() * (2.0 / PI)
     }
 
-    fn get_sqr_value(&self, frequency: f32) -> f32 {
-        if self.get_sin_value(frequency) < 0.0 {
+    fn get_sqr_value(&self, phase: f32) -> f32 {
+        if phase >= 0.5 {
             return 1.0;
         }
         
         return -1.0
     }
 
-    fn get_pulse_value(&self, frequency: f32) -> f32 {
-        let duty_cyle = 0.2;
-        if self.get_sin_value(frequency) % 1.0 < duty_cyle {
+    fn get_pulse_value(&self, phase: f32) -> f32 {
+        if phase < self.pulse_width {
             return 1.0;
         }
         
         return -1.0
     }
 
-    fn get_modulated_freq(&self, lfo_freq: f32, lfo_amplitude: f32) -> f32{
-        let base_freq = self.w(self.frequency) * self.t();
-        let lfo_part = lfo_amplitude * self.frequency * (self.w(lfo_freq) * self.t()).sin();
+    // PolyBLEP residual for a downward step of 2 at phase 0, t is the phase and dt the phase
+    // increment per sample. Only the sample either side of the step is corrected.
+    fn poly_blep(t: f32, dt: f32) -> f32 {
+        if t < dt {
+            let t = t / dt;
+            return t + t - t * t - 1.0;
+        } else if t > 1.0 - dt {
+            let t = (t - 1.0) / dt;
+            return t * t + t + t + 1.0;
+        }
+
+        return 0.0;
+    }
 
-        return base_freq + lfo_part;
+    // Integrated PolyBLEP residual for a change in slope of one per sample at phase 0
+    fn poly_blamp(t: f32, dt: f32) -> f32 {
+        let distance = if t < dt {
+            t / dt
+        } else if t > 1.0 - dt {
+            (1.0 - t) / dt
+        } else {
+            return 0.0;
+        };
+
+        return (1.0 - distance).powi(3) / 6.0;
+    }
+
+    fn get_band_limited_value(&self, phase: f32, dt: f32) -> f32 {
+        return match self.wave_type {
+            WaveType::Sin => self.get_sin_value(phase),
+            WaveType::Saw => self.get_saw_value(phase) - Self::poly_blep(phase, dt),
+            WaveType::Triangle => {
+                // Same phase as asin(sin), peaks at a quarter and three quarters of a cycle
+                // where the slope flips by 8 per cycle
+                let naive = 1.0 - 4.0 * ((phase + 0.25) % 1.0 - 0.5).abs();
+                naive - 8.0 * dt * Self::poly_blamp((phase - 0.25).rem_euclid(1.0), dt)
+                    + 8.0 * dt * Self::poly_blamp((phase - 0.75).rem_euclid(1.0), dt)
+            },
+            WaveType::Square => {
+                let naive = if phase < 0.5 { -1.0 } else { 1.0 };
+                naive - Self::poly_blep(phase, dt) + Self::poly_blep((phase + 0.5) % 1.0, dt)
+            },
+            WaveType::Pulse => {
+                let naive = if phase < self.pulse_width { 1.0 } else { -1.0 };
+                naive + Self::poly_blep(phase, dt) - Self::poly_blep((phase - self.pulse_width).rem_euclid(1.0), dt)
+            },
+            // Noise is generated in get_sample before the periodic wave forms
+            WaveType::WhiteNoise | WaveType::PinkNoise | WaveType::BrownNoise => 0.0,
+        };
+    }
+
+    // Loaded wavetables can only be played back by lookup, so they use linear interpolation
+    // unless cubic is asked for
+    fn get_wavetable_value(&self, table: &WaveTable, phase: f32, position: f32) -> f32 {
+        return match self.quality {
+            OscillatorQuality::WavetableCubic => table.get_cubic(phase, position),
+            _ => table.get_linear(phase, position),
+        };
+    }
+
+    // Any pulse width from the difference of two saws offset by the width, so the single
+    // pulse table doesn't fix the width
+    fn get_wavetable_pulse_value(&self, phase: f32) -> f32 {
+        let saw = WAVE_TABLES.get_wave_table(&WaveType::Saw);
+        let delayed = self.get_wavetable_value(saw, (phase - self.pulse_width).rem_euclid(1.0), 0.0);
+
+        return delayed - self.get_wavetable_value(saw, phase, 0.0) + 2.0 * self.pulse_width - 1.0;
+    }
+
+    // Frequency this sample once the pitch modulation and vibrato are applied, the vibrato's
+    // depth in Hz grows with both the note and the LFO frequency
+    fn get_instantaneous_frequency(&self, lfo_freq: f32, lfo_amplitude: f32) -> f32 {
+        let frequency = self.frequency * self.pitch_modulation;
+        let vibrato = lfo_amplitude * frequency * lfo_freq * (self.vibrato_phase * 2.0 * PI).cos();
+        return frequency + vibrato;
     }
 
     pub fn get_sample(&mut self, lfo_freq: f32, lfo_amplitude: f32) -> f32 {
+        return self.get_raw_sample(lfo_freq, lfo_amplitude) * self.amplitude;
+    }
 
-        let modulated_freq = self.get_modulated_freq(lfo_freq, lfo_amplitude);
+    // Next sample before the oscillator's gain
+    pub fn get_raw_sample(&mut self, lfo_freq: f32, lfo_amplitude: f32) -> f32 {
+        let frequency = self.get_instantaneous_frequency(lfo_freq, lfo_amplitude);
+        let phase = (self.phase + self.phase_modulation).rem_euclid(1.0);
 
-        let sample = match self.wave_type {
-            WaveType::Sin => self.get_sin_value(modulated_freq),
-            WaveType::Saw => self.get_saw_value(modulated_freq),
-            WaveType::Triangle => self.get_tri_value(modulated_freq),
-            WaveType::Square => self.get_sqr_value(modulated_freq),
-            WaveType::Pulse => self.get_pulse_value(modulated_freq),
+        let sample = match (&self.wavetable, self.quality, self.wave_type) {
+            (Some(table), _, _) => self.get_wavetable_value(table, phase, self.wavetable_position),
+            (None, _, WaveType::WhiteNoise) => self.noise.white(),
+            (None, _, WaveType::PinkNoise) => self.noise.pink(),
+            (None, _, WaveType::BrownNoise) => self.noise.brown(),
+            (None, OscillatorQuality::BandLimited, _) => {
+                // Phase increment per sample sizes the PolyBLEP corrections
+                let dt = (frequency.abs() / SAMPLE_RATE).min(0.5);
+                self.get_band_limited_value(phase, dt)
+            },
+            (None, OscillatorQuality::Naive, WaveType::Sin) => self.get_sin_value(phase),
+            (None, OscillatorQuality::Naive, WaveType::Saw) => self.get_saw_value(phase),
+            (None, OscillatorQuality::Naive, WaveType::Triangle) => self.get_tri_value(phase),
+            (None, OscillatorQuality::Naive, WaveType::Square) => self.get_sqr_value(phase),
+            (None, OscillatorQuality::Naive, WaveType::Pulse) => self.get_pulse_value(phase),
+            (None, _, WaveType::Pulse) => self.get_wavetable_pulse_value(phase),
+            (None, _, wave_type) => self.get_wavetable_value(WAVE_TABLES.get_wave_table(&wave_type), phase, 0.0),
         };
 
-        self.sample_index += 1.0;
-        self.sample_index %= SAMPLE_RATE;
+        self.phase_increment = frequency / SAMPLE_RATE;
+        self.wrapped = self.phase + self.phase_increment >= 1.0;
+        self.phase = (self.phase + self.phase_increment).rem_euclid(1.0);
+        self.vibrato_phase = (self.vibrato_phase + lfo_freq / SAMPLE_RATE).rem_euclid(1.0);
 
-        return sample * self.amplitude;
+        return sample;
     }
 }
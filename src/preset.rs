@@ -0,0 +1,203 @@
+use strum::{EnumCount, IntoEnumIterator};
+
+use crate::constants::*;
+use crate::synthesizer::{SynthEvent, EnvelopeParam};
+use crate::envelope::DEFAULT_SUSTAIN_AMPLITUDE;
+use crate::wavetype::{WaveType, PhaseMode, OscModulation, FmAlgorithm, FilterMode, EnvelopeCurve, EnvelopeStage};
+
+pub struct Preset {
+    pub name: &'static str,
+    pub oscillators: [SoundGenOscParams; OscNumber::COUNT],
+    pub attack: f32,
+    pub decay: f32,
+    pub release: f32,
+    pub sustain: f32,
+    // Attack, decay and release curves
+    pub envelope_shapes: [EnvelopeShape; EnvelopeStage::COUNT],
+    pub lfo: LfoParams,
+    pub fm: FmParams,
+    pub sub_osc: SubOscParams,
+    pub filter: FilterParams,
+    pub mod_envelopes: [ModEnvelopeParams; ModEnvelopeNumber::COUNT],
+    pub velocity: VelocityParams,
+}
+
+impl Preset {
+    fn init() -> Preset {
+        return Preset {
+            name: "init",
+            oscillators: SoundGenOscParams::create_default_array(),
+            attack: 0.1,
+            decay: 1.0,
+            release: 0.1,
+            sustain: DEFAULT_SUSTAIN_AMPLITUDE,
+            envelope_shapes: [EnvelopeShape::default(); EnvelopeStage::COUNT],
+            lfo: Default::default(),
+            fm: Default::default(),
+            sub_osc: Default::default(),
+            filter: Default::default(),
+            mod_envelopes: ModEnvelopeParams::create_default_array(),
+            velocity: Default::default(),
+        };
+    }
+
+    fn saw_lead() -> Preset {
+        let mut preset = Self::init();
+        preset.name = "saw_lead";
+        preset.oscillators[0].wave_type = WaveType::Saw;
+        preset.oscillators[0].unisons = 5;
+        preset.oscillators[0].unison_detune_pct = 0.1;
+        preset.oscillators[0].phase_mode = PhaseMode::Random;
+        preset.filter.mode = FilterMode::Ladder;
+        preset.filter.cutoff = 1200.0;
+        preset.filter.resonance = 0.6;
+        preset.filter.drive = 6.0;
+        preset.filter.key_tracking = 0.5;
+        preset.mod_envelopes[0].amount = 2.0;
+        preset.mod_envelopes[0].decay = 0.6;
+        preset.mod_envelopes[0].sustain = 0.3;
+        preset.attack = 0.01;
+        preset.release = 0.3;
+        preset.lfo = LfoParams { enabled: true, wave_type: WaveType::Sin, frequency: 5.0, vibrato: true };
+
+        return preset;
+    }
+
+    fn square_bass() -> Preset {
+        let mut preset = Self::init();
+        preset.name = "square_bass";
+        preset.oscillators[0].wave_type = WaveType::Square;
+        preset.sub_osc = SubOscParams { enabled: true, wave_type: WaveType::Sin, octave: -1, gain: -3.0 };
+        preset.filter.mode = FilterMode::LowPass;
+        preset.filter.cutoff = 400.0;
+        preset.filter.resonance = 0.4;
+        preset.filter.key_tracking = 0.5;
+        preset.filter.velocity_amount = 1.0;
+        preset.mod_envelopes[0].amount = 3.0;
+        preset.mod_envelopes[0].decay = 0.25;
+        preset.attack = 0.005;
+        preset.decay = 0.3;
+        preset.release = 0.05;
+        preset.envelope_shapes[EnvelopeStage::Decay as usize].curve = EnvelopeCurve::Exponential;
+        preset.velocity.curve = VelocityCurve::Hard;
+
+        return preset;
+    }
+
+    fn pad() -> Preset {
+        let mut preset = Self::init();
+        preset.name = "pad";
+        preset.oscillators[0].wave_type = WaveType::Saw;
+        preset.oscillators[0].unisons = 7;
+        preset.oscillators[0].unison_detune_pct = 0.3;
+        preset.oscillators[0].unison_spread = 1.0;
+        preset.oscillators[0].phase_mode = PhaseMode::FreeRunning;
+        preset.oscillators[1].enabled = true;
+        preset.oscillators[1].wave_type = WaveType::Triangle;
+        preset.oscillators[1].unisons = 3;
+        preset.attack = 0.8;
+        preset.decay = 2.0;
+        preset.release = 1.5;
+        preset.velocity.amplitude_amount = 0.5;
+        preset.velocity.attack_amount = 0.5;
+
+        return preset;
+    }
+
+    // Two slightly detuned pulses with their widths swept by a slow LFO
+    fn pwm_strings() -> Preset {
+        let mut preset = Self::init();
+        preset.name = "pwm_strings";
+        preset.oscillators[0].wave_type = WaveType::Pulse;
+        preset.oscillators[0].pulse_width = 0.5;
+        preset.oscillators[0].pwm_depth = 0.35;
+        preset.oscillators[0].unisons = 2;
+        preset.oscillators[0].unison_detune_pct = 0.05;
+        preset.attack = 0.6;
+        preset.decay = 2.0;
+        preset.release = 1.2;
+        preset.lfo = LfoParams { enabled: true, wave_type: WaveType::Triangle, frequency: 0.7, vibrato: false };
+        preset.velocity.amplitude_amount = 0.6;
+
+        return preset;
+    }
+
+    // A silent saw the second saw is hard synced to, a fifth above the octave for the classic sync tone
+    fn sync_lead() -> Preset {
+        let mut preset = Self::init();
+        preset.name = "sync_lead";
+        preset.oscillators[0].wave_type = WaveType::Saw;
+        preset.oscillators[0].gain = -96.0;
+        preset.oscillators[1].enabled = true;
+        preset.oscillators[1].wave_type = WaveType::Saw;
+        preset.oscillators[1].octave = 1;
+        preset.oscillators[1].semitone = 7;
+        preset.oscillators[1].modulation = OscModulation::HardSync;
+        preset.oscillators[1].modulator = OscNumber::Osc1;
+        preset.attack = 0.01;
+        preset.release = 0.2;
+
+        return preset;
+    }
+
+    // A sine modulated by a sine at an inharmonic ratio, the index dies away to leave a pure tone
+    fn fm_bell() -> Preset {
+        let mut preset = Self::init();
+        preset.name = "fm_bell";
+        preset.oscillators[1].enabled = true;
+        preset.oscillators[1].octave = 1;
+        preset.oscillators[1].semitone = 9;
+        preset.fm = FmParams { algorithm: FmAlgorithm::Pair, index: 4.0, attack: 0.0, decay: 2.0, sustain: 0.0, release: 1.0 };
+        preset.attack = 0.001;
+        preset.decay = 3.0;
+        preset.release = 1.5;
+        preset.envelope_shapes[EnvelopeStage::Decay as usize] = EnvelopeShape { curve: EnvelopeCurve::Exponential, tension: 0.8 };
+        preset.envelope_shapes[EnvelopeStage::Release as usize].curve = EnvelopeCurve::Exponential;
+
+        return preset;
+    }
+
+    pub fn get_presets() -> Vec<Preset> {
+        return vec![Self::init(), Self::saw_lead(), Self::square_bass(), Self::pad(), Self::pwm_strings(), Self::sync_lead(), Self::fm_bell()];
+    }
+
+    pub fn from_name(name: &str) -> Option<Preset> {
+        return Self::get_presets().into_iter().find(|preset| preset.name == name);
+    }
+
+    // How long a note can keep sounding after it's released, the FM and mod envelopes can
+    // outlast the amplitude envelope's release
+    pub fn get_longest_release(&self) -> f32 {
+        return self.mod_envelopes
+            .iter()
+            .map(|mod_envelope| mod_envelope.release)
+            .fold(self.release.max(self.fm.release), f32::max);
+    }
+
+    // Events that bring a synth with any settings to this preset
+    pub fn to_synth_events(&self) -> Vec<SynthEvent> {
+        let mut events: Vec<SynthEvent> = Vec::new();
+
+        for osc_params in &self.oscillators {
+            events.push(SynthEvent::ChangeSoundGenOscParams(osc_params.clone()));
+        }
+
+        events.push(SynthEvent::ChangeEnvelope(EnvelopeParam::AttackTime, self.attack));
+        events.push(SynthEvent::ChangeEnvelope(EnvelopeParam::DecayTime, self.decay));
+        events.push(SynthEvent::ChangeEnvelope(EnvelopeParam::ReleaseTime, self.release));
+        events.push(SynthEvent::ChangeEnvelope(EnvelopeParam::SustainAmplitude, self.sustain));
+        for stage in EnvelopeStage::iter() {
+            events.push(SynthEvent::ChangeEnvelopeShape(stage, self.envelope_shapes[stage as usize]));
+        }
+        events.push(SynthEvent::ChangeLfoParams(self.lfo.clone()));
+        events.push(SynthEvent::ChangeFmParams(self.fm.clone()));
+        events.push(SynthEvent::ChangeSubOscParams(self.sub_osc.clone()));
+        events.push(SynthEvent::ChangeFilterParams(self.filter.clone()));
+        for mod_envelope_params in &self.mod_envelopes {
+            events.push(SynthEvent::ChangeModEnvelopeParams(mod_envelope_params.clone()));
+        }
+        events.push(SynthEvent::ChangeVelocityParams(self.velocity.clone()));
+
+        return events;
+    }
+}
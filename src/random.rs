@@ -0,0 +1,34 @@
+// Small xorshift generator. Audio code needs cheap, seedable randomness so that
+// offline renders are reproducible, not cryptographic quality.
+#[derive(Clone)]
+pub struct Random {
+    state: u32,
+}
+
+impl Random {
+    pub fn new(seed: u32) -> Random {
+        // xorshift gets stuck on a zero state
+        let state = if seed == 0 { 0x9E37_79B9 } else { seed };
+        return Random { state };
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let mut x = self.state;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.state = x;
+
+        return x;
+    }
+
+    // Uniform value in [0.0, 1.0)
+    pub fn next_f32(&mut self) -> f32 {
+        return (self.next_u32() >> 8) as f32 / (1u32 << 24) as f32;
+    }
+
+    // Uniform value in [-1.0, 1.0)
+    pub fn next_bipolar(&mut self) -> f32 {
+        return self.next_f32() * 2.0 - 1.0;
+    }
+}
@@ -0,0 +1,299 @@
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+// Scala scale (.scl) and keyboard mapping (.kbm) files, see
+// https://www.huygens-fokker.org/scala/scl_format.html
+
+// Longest keyboard mapping pattern, far more keys than MIDI has
+pub const MAX_MAP_SIZE: usize = 1024;
+
+#[derive(Debug)]
+pub enum ScalaErrorKind {
+    Io(std::io::Error),
+    MissingLine(&'static str),
+    InvalidNumber(&'static str, String),
+    InvalidPitch(String),
+    NonPositiveRatio(String),
+    WrongNoteCount { expected: usize, found: usize },
+    EmptyScale,
+    InvalidMapEntry(String),
+    WrongMapSize { expected: usize, found: usize },
+    MapTooLarge(usize),
+}
+
+#[derive(Debug)]
+pub struct ScalaError {
+    // 1 based line number in the file, 0 when the error isn't tied to a line
+    pub line: usize,
+    pub kind: ScalaErrorKind,
+}
+
+impl ScalaError {
+    fn new(line: usize, kind: ScalaErrorKind) -> ScalaError {
+        return ScalaError { line, kind };
+    }
+}
+
+impl fmt::Display for ScalaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.line > 0 {
+            write!(f, "line {}: ", self.line)?;
+        }
+
+        return match &self.kind {
+            ScalaErrorKind::Io(error) => write!(f, "failed to read file: {}", error),
+            ScalaErrorKind::MissingLine(expected) => write!(f, "file ended before the {}", expected),
+            ScalaErrorKind::InvalidNumber(expected, found) => write!(f, "expected the {} but found '{}'", expected, found),
+            ScalaErrorKind::InvalidPitch(found) => write!(f, "expected a pitch in cents (e.g. 701.955) or a ratio (e.g. 3/2) but found '{}'", found),
+            ScalaErrorKind::NonPositiveRatio(found) => write!(f, "ratio '{}' must be greater than zero", found),
+            ScalaErrorKind::WrongNoteCount { expected, found } => write!(f, "scale declares {} notes but lists {}", expected, found),
+            ScalaErrorKind::EmptyScale => write!(f, "scale must have at least one note"),
+            ScalaErrorKind::InvalidMapEntry(found) => write!(f, "expected a scale degree or 'x' for an unmapped key but found '{}'", found),
+            ScalaErrorKind::WrongMapSize { expected, found } => write!(f, "mapping declares {} keys but lists {}", expected, found),
+            ScalaErrorKind::MapTooLarge(size) => write!(f, "mapping declares {} keys, at most {} are supported", size, MAX_MAP_SIZE),
+        };
+    }
+}
+
+impl std::error::Error for ScalaError {}
+
+// Yields (line number, line) for every line that isn't a comment
+fn content_lines(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    return text
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim_end_matches('\r')))
+        .filter(|(_, line)| !line.starts_with('!'));
+}
+
+fn first_word(line: &str) -> &str {
+    return line.split_whitespace().next().unwrap_or("");
+}
+
+fn read_file(path: &Path) -> Result<String, ScalaError> {
+    return fs::read_to_string(path).map_err(|error| ScalaError::new(0, ScalaErrorKind::Io(error)));
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scale {
+    pub description: String,
+    // Cents above the root for scale degrees 1 to n, the last one is the period (usually an octave)
+    pub degrees: Vec<f64>,
+}
+
+impl Scale {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Scale, ScalaError> {
+        return Self::parse(&read_file(path.as_ref())?);
+    }
+
+    pub fn parse(text: &str) -> Result<Scale, ScalaError> {
+        let mut lines = content_lines(text);
+
+        let (description_line, description) = lines.next().ok_or(ScalaError::new(1, ScalaErrorKind::MissingLine("description")))?;
+
+        let (line, count) = lines.next().ok_or(ScalaError::new(description_line + 1, ScalaErrorKind::MissingLine("number of notes")))?;
+        let count: usize = first_word(count)
+            .parse()
+            .map_err(|_| ScalaError::new(line, ScalaErrorKind::InvalidNumber("number of notes", count.trim().to_string())))?;
+
+        let mut last_line = line;
+
+        // Not preallocated from the count, which comes straight from the file
+        let mut degrees: Vec<f64> = Vec::new();
+        for (line, text) in lines {
+            last_line = line;
+
+            let word = first_word(text);
+            if word.is_empty() {
+                continue;
+            }
+
+            degrees.push(Self::parse_pitch(word).map_err(|kind| ScalaError::new(line, kind))?);
+        }
+
+        if degrees.len() != count {
+            return Err(ScalaError::new(last_line, ScalaErrorKind::WrongNoteCount { expected: count, found: degrees.len() }));
+        }
+
+        if count == 0 {
+            return Err(ScalaError::new(line, ScalaErrorKind::EmptyScale));
+        }
+
+        return Ok(Scale {
+            description: description.trim().to_string(),
+            degrees,
+        });
+    }
+
+    // A value with a period is in cents, anything else is a ratio or a whole number
+    fn parse_pitch(word: &str) -> Result<f64, ScalaErrorKind> {
+        let invalid = || ScalaErrorKind::InvalidPitch(word.to_string());
+
+        if word.contains('.') {
+            return word.parse::<f64>().map_err(|_| invalid());
+        }
+
+        let (numerator, denominator) = match word.split_once('/') {
+            Some((numerator, denominator)) => (numerator, denominator),
+            None => (word, "1"),
+        };
+
+        let numerator: f64 = numerator.parse::<u64>().map_err(|_| invalid())? as f64;
+        let denominator: f64 = denominator.parse::<u64>().map_err(|_| invalid())? as f64;
+
+        if numerator == 0.0 || denominator == 0.0 {
+            return Err(ScalaErrorKind::NonPositiveRatio(word.to_string()));
+        }
+
+        return Ok(1200.0 * (numerator / denominator).log2());
+    }
+
+    pub fn equal_temperament(notes: usize) -> Result<Scale, ScalaError> {
+        if notes == 0 {
+            return Err(ScalaError::new(0, ScalaErrorKind::EmptyScale));
+        }
+
+        return Ok(Scale {
+            description: format!("{} tone equal temperament", notes),
+            degrees: (1..=notes).map(|i| 1200.0 * i as f64 / notes as f64).collect(),
+        });
+    }
+
+    pub fn len(&self) -> usize {
+        return self.degrees.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.degrees.is_empty();
+    }
+
+    // Cents above the root for any degree, wrapping around the period in both directions
+    pub fn get_cents(&self, degree: i32) -> f64 {
+        let len = self.degrees.len() as i32;
+        let period = self.degrees[self.degrees.len() - 1];
+
+        let periods = degree.div_euclid(len);
+        let index = degree.rem_euclid(len);
+
+        let within_period = if index == 0 { 0.0 } else { self.degrees[index as usize - 1] };
+        return periods as f64 * period + within_period;
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyboardMapping {
+    pub first_note: i32,
+    pub last_note: i32,
+    // Key where scale degree 0 is mapped
+    pub middle_note: i32,
+    pub reference_note: i32,
+    pub reference_frequency: f64,
+    // Scale degree that counts as the formal octave, 0 uses the scale's period
+    pub octave_degree: usize,
+    // Scale degree for each key in a repeating pattern, None is an unmapped key.
+    // An empty map is a linear mapping with one degree per key.
+    pub map: Vec<Option<i32>>,
+}
+
+impl Default for KeyboardMapping {
+    fn default() -> Self {
+        Self {
+            first_note: 0,
+            last_note: 127,
+            middle_note: 60,
+            reference_note: 69,
+            reference_frequency: 440.0,
+            octave_degree: 0,
+            map: Vec::new(),
+        }
+    }
+}
+
+impl KeyboardMapping {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<KeyboardMapping, ScalaError> {
+        return Self::parse(&read_file(path.as_ref())?);
+    }
+
+    pub fn parse(text: &str) -> Result<KeyboardMapping, ScalaError> {
+        let mut lines = content_lines(text).filter(|(_, line)| !line.trim().is_empty());
+        let mut last_line = 0;
+
+        let mut next_number = |expected: &'static str| -> Result<f64, ScalaError> {
+            let (line, text) = lines.next().ok_or(ScalaError::new(last_line + 1, ScalaErrorKind::MissingLine(expected)))?;
+            last_line = line;
+
+            return first_word(text)
+                .parse::<f64>()
+                .map_err(|_| ScalaError::new(line, ScalaErrorKind::InvalidNumber(expected, text.trim().to_string())));
+        };
+
+        let map_size = next_number("map size")? as usize;
+        let first_note = next_number("first MIDI note")? as i32;
+        let last_note = next_number("last MIDI note")? as i32;
+        let middle_note = next_number("middle note")? as i32;
+        let reference_note = next_number("reference note")? as i32;
+        let reference_frequency = next_number("reference frequency")?;
+        let octave_degree = next_number("octave degree")? as usize;
+
+        let mut map: Vec<Option<i32>> = Vec::new();
+        for (line, text) in lines {
+            last_line = line;
+
+            let word = first_word(text);
+            if word == "x" || word == "X" {
+                map.push(None);
+            } else {
+                let degree = word.parse::<i32>().map_err(|_| ScalaError::new(line, ScalaErrorKind::InvalidMapEntry(word.to_string())))?;
+                map.push(Some(degree));
+            }
+        }
+
+        if map_size > MAX_MAP_SIZE {
+            return Err(ScalaError::new(0, ScalaErrorKind::MapTooLarge(map_size)));
+        }
+
+        // Trailing entries may be left out, they are unmapped
+        if map.len() > map_size {
+            return Err(ScalaError::new(last_line, ScalaErrorKind::WrongMapSize { expected: map_size, found: map.len() }));
+        }
+        map.resize(map_size, None);
+
+        return Ok(KeyboardMapping {
+            first_note,
+            last_note,
+            middle_note,
+            reference_note,
+            reference_frequency,
+            octave_degree,
+            map,
+        });
+    }
+
+    // Cents above the middle note for a key, None if the key is unmapped or out of range
+    pub fn get_cents(&self, note: i32, scale: &Scale) -> Option<f64> {
+        if note < self.first_note || note > self.last_note {
+            return None;
+        }
+
+        let offset = note - self.middle_note;
+        if self.map.is_empty() {
+            return Some(scale.get_cents(offset));
+        }
+
+        // The mapping repeats every map.len() keys, each repeat going up by the formal octave
+        let map_size = self.map.len() as i32;
+        let octave_degree = if self.octave_degree == 0 { scale.len() } else { self.octave_degree };
+        let octave_cents = scale.get_cents(octave_degree as i32);
+
+        let degree = self.map[offset.rem_euclid(map_size) as usize]?;
+        return Some(offset.div_euclid(map_size) as f64 * octave_cents + scale.get_cents(degree));
+    }
+
+    pub fn get_frequency(&self, note: i32, scale: &Scale) -> Option<f64> {
+        let cents = self.get_cents(note, scale)?;
+        let reference_cents = self.get_cents(self.reference_note, scale).unwrap_or(0.0);
+
+        return Some(self.reference_frequency * ((cents - reference_cents) / 1200.0).exp2());
+    }
+}
@@ -1,18 +1,28 @@
-use std::collections::{HashMap, VecDeque};
+use std::collections::{HashMap, HashSet, VecDeque};
 
 use strum::EnumCount;
 
-use crate::oscillator::Oscillator;
-use crate::time;
 use crate::envelope::EnvelopeADSR;
-use crate::note_generator::{NoteGenerator, NoteOscillatorParams};
+use crate::note_generator::{NoteGenerator, NoteOscillatorParams, NotePress};
 use crate::constants::*;
+use crate::tuning::Tuning;
+use crate::wavetype::{FmAlgorithm, FilterMode};
 
 pub struct SoundGenerator {
     held_notes: HashMap<i32, NoteGenerator>,
     released_notes: VecDeque<NoteGenerator>,
     finished_playing: Vec<usize>,
-    generators: [SoundGenOscParams; OscNumber::COUNT]
+    generators: [SoundGenOscParams; OscNumber::COUNT],
+    velocity_params: VelocityParams,
+    fm_params: FmParams,
+    fm_envelope: EnvelopeADSR,
+    sub_osc_params: SubOscParams,
+    filter_params: FilterParams,
+    mod_envelopes: [ModEnvelopeParams; ModEnvelopeNumber::COUNT],
+
+    pitch_bend: f32,
+    sustain_pedal: bool,
+    sustained_notes: HashSet<i32>,
 }
 
 
@@ -23,12 +33,26 @@ impl SoundGenerator {
             released_notes: VecDeque::with_capacity(MAX_NOTES),
             finished_playing: Vec::with_capacity(MAX_NOTES),
             generators: SoundGenOscParams::create_default_array(),
+            velocity_params: Default::default(),
+            fm_params: Default::default(),
+            fm_envelope: Self::create_fm_envelope(&Default::default()),
+            sub_osc_params: Default::default(),
+            filter_params: Default::default(),
+            mod_envelopes: ModEnvelopeParams::create_default_array(),
+            pitch_bend: 0.0,
+            sustain_pedal: false,
+            sustained_notes: HashSet::with_capacity(MAX_NOTES),
         }
     }
 
-    pub fn note_released(&mut self, note: i32){
+    pub fn note_released(&mut self, note: i32, sample: u64){
+        if self.sustain_pedal && self.held_notes.contains_key(&note) {
+            self.sustained_notes.insert(note);
+            return;
+        }
+
         if let Some(mut removed) = self.held_notes.remove(&note) {
-            removed.note_released();
+            removed.note_released(sample);
 
             if self.released_notes.len() >= MAX_NOTES {
                 self.released_notes.pop_front();
@@ -45,7 +69,7 @@ impl SoundGenerator {
 
         for (i, osc) in self.generators.iter().enumerate() {
             if osc.enabled {
-                osc_params[i] = Some(NoteOscillatorParams::new(osc.wave_type, osc.unisons, osc.unison_detune_pct));
+                osc_params[i] = Some(NoteOscillatorParams::new(osc));
             } else {
                 osc_params[i] = None;
             }
@@ -61,64 +85,208 @@ impl SoundGenerator {
             return None;
         }
 
-        return Some(NoteOscillatorParams::new(osc.wave_type, osc.unisons, osc.unison_detune_pct));
+        return Some(NoteOscillatorParams::new(osc));
     }
     
-    pub fn note_pressed(&mut self, note: i32){
-        let note_gen: NoteGenerator = NoteGenerator::new(note, self.get_note_params());
+    pub fn note_pressed(&mut self, note: i32, velocity: f32, tuning: &Tuning, sample: u64){
+        // Keys left out of a keyboard mapping are silent
+        if !tuning.is_mapped(note) {
+            return;
+        }
+
+        let press = NotePress {
+            velocity,
+            velocity_params: &self.velocity_params,
+            mod_envelopes: &self.mod_envelopes,
+            tuning,
+        };
+
+        let mut note_gen: NoteGenerator = NoteGenerator::new(note, &press, self.get_note_params(), self.pitch_bend, sample);
+        note_gen.set_sub_osc_params(&self.sub_osc_params, tuning);
         self.held_notes.insert(note, note_gen);
+        self.sustained_notes.remove(&note);
+    }
+
+    pub fn all_notes_released(&mut self, sample: u64){
+        let notes: Vec<i32> = self.held_notes.keys().copied().collect();
+        for note in notes {
+            self.note_released(note, sample);
+        }
+    }
+
+    // Silences everything at once, without release tails and whatever the sustain pedal is doing
+    pub fn all_sound_off(&mut self){
+        self.held_notes.clear();
+        self.sustained_notes.clear();
+        self.released_notes.clear();
+    }
+
+    // Released notes keep sounding until the pedal is lifted
+    pub fn set_sustain_pedal(&mut self, pressed: bool, sample: u64){
+        self.sustain_pedal = pressed;
+
+        if !pressed {
+            let notes: Vec<i32> = self.sustained_notes.drain().collect();
+            for note in notes {
+                self.note_released(note, sample);
+            }
+        }
+    }
+
+    // Pitch bend in semitones, applied to every sounding note
+    pub fn set_pitch_bend(&mut self, semitones: f32, tuning: &Tuning){
+        self.pitch_bend = semitones;
+        self.retune(tuning);
+    }
+
+    pub fn retune(&mut self, tuning: &Tuning){
+        for note_gen in self.held_notes.values_mut() {
+            note_gen.retune(self.pitch_bend, tuning);
+        }
+
+        for note_gen in &mut self.released_notes {
+            note_gen.retune(self.pitch_bend, tuning);
+        }
+    }
+
+    // Only affects notes pressed from now on
+    pub fn set_velocity_params(&mut self, velocity_params: VelocityParams){
+        self.velocity_params = velocity_params;
+    }
+
+    // Applies to sounding notes as well as new ones
+    pub fn set_sub_osc_params(&mut self, sub_osc_params: SubOscParams, tuning: &Tuning){
+        for note_gen in self.held_notes.values_mut() {
+            note_gen.set_sub_osc_params(&sub_osc_params, tuning);
+        }
+
+        for note_gen in &mut self.released_notes {
+            note_gen.set_sub_osc_params(&sub_osc_params, tuning);
+        }
+
+        self.sub_osc_params = sub_osc_params;
+    }
+
+    pub fn set_fm_params(&mut self, fm_params: FmParams){
+        self.fm_envelope = Self::create_fm_envelope(&fm_params);
+        self.fm_params = fm_params;
+    }
+
+    fn create_fm_envelope(fm_params: &FmParams) -> EnvelopeADSR {
+        let mut envelope = EnvelopeADSR::with_levels(1.0, fm_params.sustain);
+        envelope.set_attack_time(fm_params.attack);
+        envelope.set_decay_time(fm_params.decay);
+        envelope.set_release_time(fm_params.release);
+
+        return envelope;
+    }
+
+    fn get_fm_index(fm_params: &FmParams, fm_envelope: &EnvelopeADSR, note_gen: &NoteGenerator, sample: u64) -> f32 {
+        if fm_params.algorithm == FmAlgorithm::Off {
+            return 0.0;
+        }
+
+        return fm_params.index * note_gen.get_envelope_level(fm_envelope, sample, 1.0);
+    }
+
+    pub fn set_filter_params(&mut self, filter_params: FilterParams){
+        self.filter_params = filter_params;
+    }
+
+    pub fn set_filter_param(&mut self, param: FilterParam, value: f32){
+        let filter_params = &mut self.filter_params;
+
+        match param {
+            FilterParam::Cutoff => filter_params.cutoff = value,
+            FilterParam::Resonance => filter_params.resonance = value.clamp(0.0, 1.0),
+            FilterParam::Drive => filter_params.drive = value.max(0.0),
+            FilterParam::KeyTracking => filter_params.key_tracking = value,
+            FilterParam::VelocityAmount => filter_params.velocity_amount = value,
+        }
+    }
+
+    pub fn set_filter_mode(&mut self, mode: FilterMode){
+        self.filter_params.mode = mode;
     }
 
-    pub fn update_oscillator_params(&mut self, osc_params: SoundGenOscParams){
-        let osc = &mut self.generators[osc_params.num as usize];
+    // Applies to sounding notes as well as new ones
+    pub fn set_mod_envelope_params(&mut self, mod_envelope_params: ModEnvelopeParams){
+        for note_gen in self.held_notes.values_mut() {
+            note_gen.set_mod_envelope_params(&mod_envelope_params);
+        }
+
+        for note_gen in &mut self.released_notes {
+            note_gen.set_mod_envelope_params(&mod_envelope_params);
+        }
+
+        let num = mod_envelope_params.num as usize;
+        self.mod_envelopes[num] = mod_envelope_params;
+    }
 
-        osc.enabled = osc_params.enabled;
-        osc.wave_type = osc_params.wave_type;
-        osc.unisons = osc_params.unisons;
-        osc.unison_detune_pct = osc_params.unison_detune_pct;
+    pub fn update_oscillator_params(&mut self, osc_params: SoundGenOscParams, tuning: &Tuning, sample: u64){
+        let osc_num = osc_params.num as usize;
+        self.generators[osc_num] = osc_params;
 
-        self.update_note_params(osc_params.num as usize);
+        self.update_note_params(osc_num, tuning, sample);
     }
 
-    fn update_note_params(&mut self, osc_num: usize){
+    pub fn set_oscillator_param(&mut self, osc_num: OscNumber, param: OscillatorParam, value: f32, tuning: &Tuning, sample: u64){
+        let osc = &mut self.generators[osc_num as usize];
+
+        match param {
+            OscillatorParam::UnisonSpread => osc.unison_spread = value.clamp(0.0, 1.0),
+            OscillatorParam::StartPhase => osc.start_phase = value.rem_euclid(1.0),
+            OscillatorParam::Gain => osc.gain = value,
+            OscillatorParam::Pan => osc.pan = value.clamp(-1.0, 1.0),
+            OscillatorParam::Octave => osc.octave = value.round() as i32,
+            OscillatorParam::Semitone => osc.semitone = value.round() as i32,
+            OscillatorParam::FineTune => osc.fine_tune_cents = value,
+        }
+
+        self.update_note_params(osc_num as usize, tuning, sample);
+    }
+
+    fn update_note_params(&mut self, osc_num: usize, tuning: &Tuning, sample: u64){
         let note_params = self.get_note_params_for_osc(osc_num);
 
         match note_params {
             Some(param) => {
                 for note_gen in &mut self.held_notes {
-                    note_gen.1.set_note_params(osc_num, &param);
+                    note_gen.1.set_note_params(osc_num, &param, tuning, sample);
                 }
         
                 for note_gen in &mut self.released_notes {
-                    note_gen.set_note_params(osc_num, &param);
+                    note_gen.set_note_params(osc_num, &param, tuning, sample);
                 }
             },
             None => {},
         }
     }
 
-    pub fn get_sample(&mut self, envelope: &EnvelopeADSR, lfo: Option<&Oscillator>) -> f32 {
-        let mut total = 0.0;
-        let time = time::get_time();
-
-        let mut lfo_freq = 0.0;
-        let mut lfo_amplitude = 0.0;
-
-        if let Some(lfo_osc) = lfo {
-            lfo_freq = lfo_osc.get_frequency();
-            lfo_amplitude = lfo_osc.get_amplitude();
-        } 
+    pub fn get_frame(&mut self, envelope: &EnvelopeADSR, lfo_freq: f32, lfo_amplitude: f32, lfo_value: f32, sample: u64) -> [f32; 2] {
+        let mut total = [0.0, 0.0];
 
         for note_gen in &mut self.held_notes {
-            let amplitude = envelope.get_amplitude(time, note_gen.1.trigger_on_time, note_gen.1.trigger_off_time, note_gen.1.note_pressed);
-            total += note_gen.1.get_sample(lfo_freq, lfo_amplitude) * amplitude;
+            let amplitude = note_gen.1.get_envelope_level(envelope, sample, note_gen.1.attack_scale) * note_gen.1.velocity_amplitude;
+            let fm_index = Self::get_fm_index(&self.fm_params, &self.fm_envelope, note_gen.1, sample);
+            note_gen.1.update_envelopes(sample);
+            let frame = note_gen.1.get_frame(lfo_freq, lfo_amplitude, lfo_value, self.fm_params.algorithm, fm_index);
+            let [left, right] = note_gen.1.apply_filter(frame, &self.filter_params);
+            total[0] += left * amplitude;
+            total[1] += right * amplitude;
         }
 
         for (i, note_gen) in  self.released_notes.iter_mut().enumerate() {
             
-            let amplitude = envelope.get_amplitude(time, note_gen.trigger_on_time, note_gen.trigger_off_time, note_gen.note_pressed);
+            let amplitude = note_gen.get_envelope_level(envelope, sample, note_gen.attack_scale);
             if amplitude > 0.0 {
-                total += note_gen.get_sample(lfo_freq, lfo_amplitude) * amplitude;
+                let amplitude = amplitude * note_gen.velocity_amplitude;
+                let fm_index = Self::get_fm_index(&self.fm_params, &self.fm_envelope, note_gen, sample);
+                note_gen.update_envelopes(sample);
+                let frame = note_gen.get_frame(lfo_freq, lfo_amplitude, lfo_value, self.fm_params.algorithm, fm_index);
+                let [left, right] = note_gen.apply_filter(frame, &self.filter_params);
+                total[0] += left * amplitude;
+                total[1] += right * amplitude;
             }
             else {
                 if self.finished_playing.len() <= MAX_NOTES {
@@ -1,4 +1,4 @@
-use std::sync::mpsc::Receiver;
+use std::sync::mpsc::{Receiver, channel};
 use std::time::Duration;
 
 use rodio::Source;
@@ -6,23 +6,78 @@ use rodio::Source;
 use crate::constants::*;
 use crate::envelope::EnvelopeADSR;
 use crate::oscillator::Oscillator;
+use crate::preset::Preset;
+use crate::scala::{Scale, KeyboardMapping};
 use crate::sound_generator::SoundGenerator;
-use crate::wavetype::WaveType;
+use crate::time::SampleClock;
+use crate::tuning::Tuning;
+use crate::wavetype::{WaveType, FilterMode, EnvelopeStage};
 
+const PITCH_BEND_RANGE: f32 = 2.0;
+const LFO_VIBRATO_GAIN: f32 = -25.0;
+const MODULATION_VIBRATO_GAIN: f32 = -25.0;
 
+const CC_MOD_WHEEL: u8 = 1;
+const CC_SUSTAIN_PEDAL: u8 = 64;
+const CC_ALL_SOUND_OFF: u8 = 120;
+const CC_ALL_NOTES_OFF: u8 = 123;
 
+// Times in seconds, sustain as an amplitude up to the envelope's peak of DEFAULT_START_AMPLITUDE
 pub enum EnvelopeParam {
     AttackTime,
     DecayTime,
-    ReleaseTime
+    ReleaseTime,
+    SustainAmplitude
+}
+
+pub enum TuningParam {
+    ReferenceFrequency,
+    Transpose,
+    FineTune
 }
 
 pub enum SynthEvent {
-    NotePress (i32),
+    // MIDI note number and velocity from 0.0 to 1.0
+    NotePress (i32, f32),
     NoteRelease (i32),
     ChangeSoundGenOscParams (SoundGenOscParams),
+    ChangeOscillator (OscNumber, OscillatorParam, f32),
     ChangeEnvelope (EnvelopeParam, f32),
-    ChangeLfoParams (LfoParams)
+    ChangeEnvelopeShape (EnvelopeStage, EnvelopeShape),
+    ChangeLfoParams (LfoParams),
+    ChangeFmParams (FmParams),
+    ChangeSubOscParams (SubOscParams),
+    ChangeFilterParams (FilterParams),
+    ChangeFilter (FilterParam, f32),
+    ChangeFilterMode (FilterMode),
+    ChangeModEnvelopeParams (ModEnvelopeParams),
+    ChangeVelocityParams (VelocityParams),
+    ChangeTuning (TuningParam, f32),
+    // None goes back to 12 tone equal temperament
+    ChangeScale (Option<Scale>),
+    ChangeKeyboardMapping (Option<KeyboardMapping>),
+    // -1.0 to 1.0 of the pitch bend range
+    PitchBend (f32),
+    // Controller number and value as sent over MIDI
+    ControlChange (u8, u8),
+    // 0.0 to 1.0
+    ChannelPressure (f32),
+    ProgramChange (u8),
+}
+
+pub struct TimedSynthEvent {
+    pub sample_offset: u64,
+    pub event: SynthEvent,
+}
+
+impl TimedSynthEvent {
+    pub fn new(time: f32, event: SynthEvent) -> TimedSynthEvent {
+        return Self::at_sample(SampleClock::time_to_samples(time), event);
+    }
+
+    pub fn at_sample(sample_offset: u64, event: SynthEvent) -> TimedSynthEvent {
+        return TimedSynthEvent { sample_offset, event };
+    }
 }
 
 
@@ -32,21 +87,42 @@ pub struct Synthesizer {
 
     envelope: EnvelopeADSR, 
     lfo: Oscillator,
+    lfo_params: LfoParams,
+    tuning: Tuning,
+    mod_wheel: f32,
+    channel_pressure: f32,
+    // Built once so program changes don't construct every preset on the audio thread
+    presets: Vec<Preset>,
+
+    clock: SampleClock,
+    // Right channel of the last frame, waiting to be interleaved after the left
+    pending_right: Option<f32>,
 }
 
 impl Synthesizer {
     pub fn new(receiver: Receiver<SynthEvent>) -> Synthesizer {
-        let mut lfo = Oscillator::new(2.0, WaveType::Sin, 0.0);
-        lfo.set_gain(f32::MIN);
+        let lfo = Oscillator::new(2.0, WaveType::Sin, 0.0);
 
         return Synthesizer{
             receiver: receiver,
             sound_generator: SoundGenerator::new(),
             envelope: EnvelopeADSR::new(),
-            lfo: lfo
+            lfo: lfo,
+            lfo_params: Default::default(),
+            tuning: Default::default(),
+            mod_wheel: 0.0,
+            channel_pressure: 0.0,
+            presets: Preset::get_presets(),
+            clock: SampleClock::new(),
+            pending_right: None,
         };
     }
 
+    pub fn new_offline() -> Synthesizer {
+        let (_, receiver) = channel();
+        return Self::new(receiver);
+    }
+
     fn set_attack_time(&mut self, attack: f32){
         self.envelope.set_attack_time(attack);
     }
@@ -59,47 +135,155 @@ impl Synthesizer {
         self.envelope.set_release_time(release);
     }
 
+    fn set_sustain_amplitude(&mut self, sustain: f32){
+        self.envelope.set_sustain_amplitude(sustain);
+    }
+
     fn handle_events(&mut self) {
         if let Ok(event) = self.receiver.try_recv(){
-            match event {
-                SynthEvent::NotePress(note) => self.sound_generator.note_pressed(note),
-                SynthEvent::NoteRelease(note) => self.sound_generator.note_released(note),
-                SynthEvent::ChangeSoundGenOscParams(osc_params) => self.sound_generator.update_oscillator_params(osc_params),
-                SynthEvent::ChangeEnvelope(param, value) => {
-                    match param {
-                        EnvelopeParam::AttackTime => self.set_attack_time(value),
-                        EnvelopeParam::DecayTime => self.set_decay_time(value),
-                        EnvelopeParam::ReleaseTime => self.set_release_time(value),
-                    }
-                },
-                SynthEvent::ChangeLfoParams(lfo_params) => {
-                    self.lfo.set_wave_type(lfo_params.wave_type);
-                    self.lfo.set_frequency(lfo_params.frequency);
-                    
-                    if !lfo_params.enabled {
-                        self.lfo.set_gain(f32::MIN);
-                    } else {
-                        self.lfo.set_gain(-25.0);
-                    }
-                },
-            }
+            self.handle_event(event);
+        }
+    }
+
+    pub fn handle_event(&mut self, event: SynthEvent) {
+        match event {
+            SynthEvent::NotePress(note, velocity) => self.sound_generator.note_pressed(note, velocity, &self.tuning, self.clock.get_sample_count()),
+            SynthEvent::NoteRelease(note) => self.sound_generator.note_released(note, self.clock.get_sample_count()),
+            SynthEvent::ChangeSoundGenOscParams(osc_params) => self.sound_generator.update_oscillator_params(osc_params, &self.tuning, self.clock.get_sample_count()),
+            SynthEvent::ChangeOscillator(osc_num, param, value) => self.sound_generator.set_oscillator_param(osc_num, param, value, &self.tuning, self.clock.get_sample_count()),
+            SynthEvent::ChangeEnvelope(param, value) => {
+                match param {
+                    EnvelopeParam::AttackTime => self.set_attack_time(value),
+                    EnvelopeParam::DecayTime => self.set_decay_time(value),
+                    EnvelopeParam::ReleaseTime => self.set_release_time(value),
+                    EnvelopeParam::SustainAmplitude => self.set_sustain_amplitude(value),
+                }
+            },
+            SynthEvent::ChangeEnvelopeShape(stage, shape) => self.envelope.set_shape(stage, shape),
+            SynthEvent::ChangeLfoParams(lfo_params) => {
+                self.lfo.set_wave_type(lfo_params.wave_type);
+                self.lfo.set_frequency(lfo_params.frequency);
+                self.lfo_params = lfo_params;
+            },
+            SynthEvent::ChangeFmParams(fm_params) => self.sound_generator.set_fm_params(fm_params),
+            SynthEvent::ChangeSubOscParams(sub_osc_params) => self.sound_generator.set_sub_osc_params(sub_osc_params, &self.tuning),
+            SynthEvent::ChangeFilterParams(filter_params) => self.sound_generator.set_filter_params(filter_params),
+            SynthEvent::ChangeFilter(param, value) => self.sound_generator.set_filter_param(param, value),
+            SynthEvent::ChangeFilterMode(mode) => self.sound_generator.set_filter_mode(mode),
+            SynthEvent::ChangeModEnvelopeParams(mod_envelope_params) => self.sound_generator.set_mod_envelope_params(mod_envelope_params),
+            SynthEvent::ChangeVelocityParams(velocity_params) => self.sound_generator.set_velocity_params(velocity_params),
+            SynthEvent::ChangeTuning(param, value) => {
+                match param {
+                    TuningParam::ReferenceFrequency => self.tuning.set_a4_frequency(value),
+                    TuningParam::Transpose => self.tuning.set_transpose(value),
+                    TuningParam::FineTune => self.tuning.set_fine_tune(value),
+                }
+
+                self.sound_generator.retune(&self.tuning);
+            },
+            SynthEvent::ChangeScale(scale) => {
+                self.tuning.set_scale(scale);
+                self.sound_generator.retune(&self.tuning);
+            },
+            SynthEvent::ChangeKeyboardMapping(keyboard_mapping) => {
+                self.tuning.set_keyboard_mapping(keyboard_mapping);
+                self.sound_generator.retune(&self.tuning);
+            },
+            SynthEvent::PitchBend(bend) => self.sound_generator.set_pitch_bend(bend * PITCH_BEND_RANGE, &self.tuning),
+            SynthEvent::ControlChange(controller, value) => self.handle_control_change(controller, value),
+            SynthEvent::ChannelPressure(pressure) => self.channel_pressure = pressure,
+            SynthEvent::ProgramChange(program) => {
+                let events = self.presets[program as usize % self.presets.len()].to_synth_events();
+                for event in events {
+                    self.handle_event(event);
+                }
+            },
+        }
+    }
+
+    fn handle_control_change(&mut self, controller: u8, value: u8) {
+        match controller {
+            CC_MOD_WHEEL => self.mod_wheel = value as f32 / 127.0,
+            CC_SUSTAIN_PEDAL => self.sound_generator.set_sustain_pedal(value >= 64, self.clock.get_sample_count()),
+            CC_ALL_SOUND_OFF => self.sound_generator.all_sound_off(),
+            CC_ALL_NOTES_OFF => self.sound_generator.all_notes_released(self.clock.get_sample_count()),
+            _ => {},
         }
     }
 
+    // Mod wheel and aftertouch add vibrato at the LFO rate on top of the LFO's own depth
+    fn get_vibrato_amplitude(&self) -> f32 {
+        let lfo_depth = if self.lfo_params.enabled && self.lfo_params.vibrato {
+            (10.0 as f32).powf(LFO_VIBRATO_GAIN / 20.0)
+        } else {
+            0.0
+        };
+
+        let modulation = self.mod_wheel.max(self.channel_pressure);
+        return lfo_depth + modulation * (10.0 as f32).powf(MODULATION_VIBRATO_GAIN / 20.0);
+    }
+
+    // LFO wave form from -1.0 to 1.0 for modulating oscillator parameters, 0.0 while disabled
+    fn get_lfo_value(&mut self) -> f32 {
+        if !self.lfo_params.enabled {
+            return 0.0;
+        }
+
+        return self.lfo.get_sample(0.0, 0.0);
+    }
+
+    pub fn get_time(&self) -> f32 {
+        return self.clock.get_time();
+    }
+
+    pub fn get_sample_count(&self) -> u64 {
+        return self.clock.get_sample_count();
+    }
 
-    pub fn get_synth_sample(&mut self) -> f32 {
+    // Left and right samples for the next frame
+    pub fn get_synth_frame(&mut self) -> [f32; 2] {
         self.handle_events();
 
-        let sample = self.sound_generator.get_sample(&self.envelope, Some(&self.lfo));
-        return sample;
+        let lfo_value = self.get_lfo_value();
+        let frame = self.sound_generator.get_frame(&self.envelope, self.lfo.get_frequency(), self.get_vibrato_amplitude(), lfo_value, self.clock.get_sample_count());
+        self.clock.tick();
+
+        return frame;
+    }
+
+    // Renders `duration` seconds as fast as possible, applying each event at its
+    // sample offset relative to the start of the render. Frames are interleaved left then right.
+    pub fn render(&mut self, mut events: Vec<TimedSynthEvent>, duration: f32) -> Vec<f32> {
+        let num_samples = SampleClock::time_to_samples(duration) as usize;
+        let mut buffer: Vec<f32> = Vec::with_capacity(num_samples * NUM_CHANNELS as usize);
+
+        events.sort_by_key(|e| e.sample_offset);
+        let mut events = events.into_iter().peekable();
+
+        for i in 0..num_samples {
+            while let Some(timed_event) = events.next_if(|e| e.sample_offset <= i as u64) {
+                self.handle_event(timed_event.event);
+            }
+
+            buffer.extend(self.get_synth_frame());
+        }
+
+        return buffer;
     }
 }
 
 impl Iterator for Synthesizer {
     type Item = f32;
 
+    // Interleaves the frames, left then right
     fn next(&mut self) -> Option<f32>{
-        return Some(self.get_synth_sample());
+        if let Some(right) = self.pending_right.take() {
+            return Some(right);
+        }
+
+        let [left, right] = self.get_synth_frame();
+        self.pending_right = Some(right);
+        return Some(left);
     }
 }
 
@@ -1,10 +1,33 @@
-use std::time::Instant;
-use lazy_static::lazy_static;
+use crate::constants::SAMPLE_RATE;
 
-lazy_static! {
-    static ref START_TIME: Instant = Instant::now();
+// Counts rendered samples so that envelope timing depends only on how much audio
+// has been produced, not on when the audio thread happens to be scheduled.
+pub struct SampleClock {
+    sample_count: u64,
 }
 
-pub fn get_time() -> f32 {
-    START_TIME.elapsed().as_secs_f32()
-}
\ No newline at end of file
+impl SampleClock {
+    pub fn new() -> SampleClock {
+        return SampleClock { sample_count: 0 };
+    }
+
+    pub fn get_sample_count(&self) -> u64 {
+        return self.sample_count;
+    }
+
+    pub fn get_time(&self) -> f32 {
+        return Self::samples_to_time(self.sample_count);
+    }
+
+    pub fn tick(&mut self) {
+        self.sample_count += 1;
+    }
+
+    pub fn samples_to_time(samples: u64) -> f32 {
+        return (samples as f64 / SAMPLE_RATE as f64) as f32;
+    }
+
+    pub fn time_to_samples(time: f32) -> u64 {
+        return (time.max(0.0) as f64 * SAMPLE_RATE as f64).round() as u64;
+    }
+}
@@ -0,0 +1,98 @@
+use std::borrow::Cow;
+
+use crate::scala::{Scale, KeyboardMapping};
+
+pub const A4_MIDI_NOTE: i32 = 69;
+pub const DEFAULT_A4_FREQUENCY: f32 = 440.0;
+
+// Maps MIDI note numbers (69 = A4) to frequencies, in 12 tone equal temperament unless a
+// Scala scale is loaded
+#[derive(Clone)]
+pub struct Tuning {
+    a4_frequency: f32,
+    transpose: i32,
+    fine_tune_cents: f32,
+    scale: Option<Scale>,
+    // Only used with a scale, a scale without a mapping is laid out from middle C with A4
+    // at the reference frequency
+    keyboard_mapping: Option<KeyboardMapping>,
+}
+
+impl Default for Tuning {
+    fn default() -> Self {
+        Self {
+            a4_frequency: DEFAULT_A4_FREQUENCY,
+            transpose: 0,
+            fine_tune_cents: 0.0,
+            scale: None,
+            keyboard_mapping: None,
+        }
+    }
+}
+
+impl Tuning {
+    pub fn new(a4_frequency: f32) -> Tuning {
+        return Tuning { a4_frequency, ..Default::default() };
+    }
+
+    // Transpose shifts keys, so with a scale loaded it moves by scale degrees
+    pub fn get_frequency(&self, note: i32) -> f32 {
+        let fine_tune = (self.fine_tune_cents / 1200.0).exp2();
+        return self.get_untuned_frequency(note + self.transpose) * fine_tune;
+    }
+
+    // False for keys a keyboard mapping leaves unmapped, these shouldn't sound
+    pub fn is_mapped(&self, note: i32) -> bool {
+        return match &self.scale {
+            Some(scale) => self.get_keyboard_mapping().get_cents(note + self.transpose, scale).is_some(),
+            None => true,
+        };
+    }
+
+    fn get_untuned_frequency(&self, note: i32) -> f32 {
+        let scale = match &self.scale {
+            Some(scale) => scale,
+            None => return self.a4_frequency * ((note - A4_MIDI_NOTE) as f32 / 12.0).exp2(),
+        };
+
+        return self.get_keyboard_mapping().get_frequency(note, scale).unwrap_or(0.0) as f32;
+    }
+
+    // Called for every note, so a loaded mapping is borrowed rather than cloned
+    fn get_keyboard_mapping(&self) -> Cow<'_, KeyboardMapping> {
+        return match &self.keyboard_mapping {
+            Some(mapping) => Cow::Borrowed(mapping),
+            None => Cow::Owned(KeyboardMapping { reference_frequency: self.a4_frequency as f64, ..Default::default() }),
+        };
+    }
+
+    pub fn get_a4_frequency(&self) -> f32 {
+        return self.a4_frequency;
+    }
+
+    pub fn set_a4_frequency(&mut self, a4_frequency: f32) {
+        self.a4_frequency = a4_frequency;
+    }
+
+    pub fn set_transpose(&mut self, semitones: f32) {
+        self.transpose = semitones.round() as i32;
+    }
+
+    pub fn set_fine_tune(&mut self, cents: f32) {
+        self.fine_tune_cents = cents;
+    }
+
+    pub fn get_scale(&self) -> Option<&Scale> {
+        return self.scale.as_ref();
+    }
+
+    // None goes back to equal temperament
+    pub fn set_scale(&mut self, scale: Option<Scale>) {
+        self.scale = scale;
+    }
+
+    // A mapping's reference frequency takes over from the A4 frequency
+    pub fn set_keyboard_mapping(&mut self, keyboard_mapping: Option<KeyboardMapping>) {
+        self.keyboard_mapping = keyboard_mapping;
+    }
+}
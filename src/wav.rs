@@ -0,0 +1,475 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use rodio::Source;
+
+use crate::constants::*;
+use crate::random::Random;
+
+const WAVE_FORMAT_PCM: u16 = 1;
+const WAVE_FORMAT_IEEE_FLOAT: u16 = 3;
+const WAVE_FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+
+const HEADER_SIZE: u32 = 44;
+// Largest data chunk whose size still fits the RIFF chunk size
+const MAX_DATA_BYTES: u32 = u32::MAX - (HEADER_SIZE - 8);
+// Samples the recorder collects before handing them to the writer thread
+const RECORDER_BLOCK_SIZE: usize = 4096;
+// How long finishing a recording waits for the audio thread to hand over its last block
+const RECORDER_FINISH_TIMEOUT: Duration = Duration::from_millis(500);
+const DITHER_SEED: u32 = 0x5EED_D17E;
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum WavFormat {
+    Int16,
+    Int24,
+    Float32,
+}
+
+impl WavFormat {
+    pub fn bits_per_sample(&self) -> u16 {
+        return match self {
+            WavFormat::Int16 => 16,
+            WavFormat::Int24 => 24,
+            WavFormat::Float32 => 32,
+        };
+    }
+
+    fn format_tag(&self) -> u16 {
+        return match self {
+            WavFormat::Int16 | WavFormat::Int24 => WAVE_FORMAT_PCM,
+            WavFormat::Float32 => WAVE_FORMAT_IEEE_FLOAT,
+        };
+    }
+
+    fn max_int_value(&self) -> f32 {
+        return match self {
+            WavFormat::Int16 => i16::MAX as f32,
+            WavFormat::Int24 => 8_388_607.0,
+            WavFormat::Float32 => 1.0,
+        };
+    }
+}
+
+// Writes NUM_CHANNELS channel files, samples are expected interleaved one frame at a time
+pub struct WavWriter<W: Write + Seek> {
+    writer: W,
+    format: WavFormat,
+    dither: Option<Random>,
+    data_bytes: u32,
+    finalized: bool,
+}
+
+impl WavWriter<BufWriter<File>> {
+    pub fn create<P: AsRef<Path>>(path: P, format: WavFormat, dither: bool) -> io::Result<WavWriter<BufWriter<File>>> {
+        let file = File::create(path)?;
+        return WavWriter::new(BufWriter::new(file), format, dither);
+    }
+}
+
+impl<W: Write + Seek> WavWriter<W> {
+    // Dither is only applied to integer formats, float output is written as is.
+    pub fn new(writer: W, format: WavFormat, dither: bool) -> io::Result<WavWriter<W>> {
+        let dither = if dither && format != WavFormat::Float32 {
+            Some(Random::new(DITHER_SEED))
+        } else {
+            None
+        };
+
+        let mut wav_writer = WavWriter {
+            writer,
+            format,
+            dither,
+            data_bytes: 0,
+            finalized: false,
+        };
+
+        wav_writer.write_header()?;
+        return Ok(wav_writer);
+    }
+
+    fn write_header(&mut self) -> io::Result<()> {
+        let channels = NUM_CHANNELS;
+        let sample_rate = SAMPLE_RATE as u32;
+        let block_align = channels * (self.format.bits_per_sample() / 8);
+        let byte_rate = sample_rate * block_align as u32;
+
+        let w = &mut self.writer;
+        w.write_all(b"RIFF")?;
+        w.write_all(&(HEADER_SIZE - 8 + self.data_bytes).to_le_bytes())?;
+        w.write_all(b"WAVE")?;
+
+        w.write_all(b"fmt ")?;
+        w.write_all(&16u32.to_le_bytes())?;
+        w.write_all(&self.format.format_tag().to_le_bytes())?;
+        w.write_all(&channels.to_le_bytes())?;
+        w.write_all(&sample_rate.to_le_bytes())?;
+        w.write_all(&byte_rate.to_le_bytes())?;
+        w.write_all(&block_align.to_le_bytes())?;
+        w.write_all(&self.format.bits_per_sample().to_le_bytes())?;
+
+        w.write_all(b"data")?;
+        w.write_all(&self.data_bytes.to_le_bytes())?;
+
+        return Ok(());
+    }
+
+    fn quantize(&mut self, sample: f32) -> i32 {
+        let max = self.format.max_int_value();
+        let mut scaled = sample * max;
+
+        if let Some(rng) = &mut self.dither {
+            // TPDF: sum of two uniform values gives a triangular distribution of +/- 1 LSB
+            scaled += rng.next_f32() - rng.next_f32();
+        }
+
+        return scaled.round().clamp(-max - 1.0, max) as i32;
+    }
+
+    pub fn write_sample(&mut self, sample: f32) -> io::Result<()> {
+        let data_bytes = self.data_bytes
+            .checked_add((self.format.bits_per_sample() / 8) as u32)
+            .filter(|data_bytes| *data_bytes <= MAX_DATA_BYTES)
+            .ok_or_else(|| io::Error::other("WAV file is at its 4GB size limit"))?;
+
+        match self.format {
+            WavFormat::Int16 => {
+                let value = self.quantize(sample) as i16;
+                self.writer.write_all(&value.to_le_bytes())?;
+            },
+            WavFormat::Int24 => {
+                let value = self.quantize(sample);
+                self.writer.write_all(&value.to_le_bytes()[0..3])?;
+            },
+            WavFormat::Float32 => {
+                self.writer.write_all(&sample.to_le_bytes())?;
+            },
+        }
+
+        self.data_bytes = data_bytes;
+        return Ok(());
+    }
+
+    pub fn write_samples(&mut self, samples: &[f32]) -> io::Result<()> {
+        for sample in samples {
+            self.write_sample(*sample)?;
+        }
+
+        return Ok(());
+    }
+
+    fn update_header(&mut self) -> io::Result<()> {
+        self.writer.seek(SeekFrom::Start(0))?;
+        self.write_header()?;
+        self.writer.seek(SeekFrom::End(0))?;
+        self.writer.flush()?;
+
+        return Ok(());
+    }
+
+    // Patches the chunk sizes into the header. Dropping the writer does the same but
+    // has to swallow any error.
+    pub fn finalize(mut self) -> io::Result<()> {
+        self.finalized = true;
+        return self.update_header();
+    }
+}
+
+impl<W: Write + Seek> Drop for WavWriter<W> {
+    fn drop(&mut self) {
+        if !self.finalized {
+            let _ = self.update_header();
+        }
+    }
+}
+
+pub fn write_wav<P: AsRef<Path>>(path: P, samples: &[f32], format: WavFormat, dither: bool) -> io::Result<()> {
+    let mut writer = WavWriter::create(path, format, dither)?;
+    writer.write_samples(samples)?;
+
+    return writer.finalize();
+}
+
+// Contents of a WAV file with every channel mixed down to mono
+pub struct WavData {
+    pub sample_rate: u32,
+    pub samples: Vec<f32>,
+    // Samples per wavetable frame from the 'clm ' chunk Serum and other wavetable synths write
+    pub frame_size: Option<usize>,
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    return io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+}
+
+fn read_le_u16(bytes: &[u8], offset: usize) -> u16 {
+    return u16::from_le_bytes([bytes[offset], bytes[offset + 1]]);
+}
+
+fn read_le_u32(bytes: &[u8], offset: usize) -> u32 {
+    return u32::from_le_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]]);
+}
+
+fn decode_sample(bytes: &[u8], format_tag: u16, bits_per_sample: u16) -> Option<f32> {
+    return match (format_tag, bits_per_sample) {
+        (WAVE_FORMAT_PCM, 8) => Some((bytes[0] as f32 - 128.0) / 128.0),
+        (WAVE_FORMAT_PCM, 16) => Some(i16::from_le_bytes([bytes[0], bytes[1]]) as f32 / 32768.0),
+        (WAVE_FORMAT_PCM, 24) => Some(i32::from_le_bytes([0, bytes[0], bytes[1], bytes[2]]) as f32 / 2_147_483_648.0),
+        (WAVE_FORMAT_PCM, 32) => Some(i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f32 / 2_147_483_648.0),
+        (WAVE_FORMAT_IEEE_FLOAT, 32) => Some(f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])),
+        (WAVE_FORMAT_IEEE_FLOAT, 64) => {
+            let mut value = [0u8; 8];
+            value.copy_from_slice(&bytes[0..8]);
+            Some(f64::from_le_bytes(value) as f32)
+        },
+        _ => None,
+    };
+}
+
+// The 'clm ' chunk is text starting with "<!>2048", the number being the frame size
+fn parse_frame_size(chunk: &[u8]) -> Option<usize> {
+    let text = std::str::from_utf8(chunk).ok()?;
+    let digits: String = text.strip_prefix("<!>")?.chars().take_while(|c| c.is_ascii_digit()).collect();
+
+    return digits.parse().ok().filter(|frame_size| *frame_size > 0);
+}
+
+// Reads 8 to 32 bit integer and 32 or 64 bit float files, including the extensible format
+pub fn parse_wav(bytes: &[u8]) -> io::Result<WavData> {
+    if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+        return Err(invalid_data("not a RIFF WAVE file"));
+    }
+
+    let mut format: Option<(u16, u16, u32, u16)> = None;
+    let mut data: Option<&[u8]> = None;
+    let mut frame_size = None;
+
+    let mut offset = 12;
+    while offset + 8 <= bytes.len() {
+        let id = &bytes[offset..offset + 4];
+        let size = read_le_u32(bytes, offset + 4) as usize;
+        let start = offset + 8;
+        let end = (start + size).min(bytes.len());
+        let chunk = &bytes[start..end];
+
+        match id {
+            b"fmt " => {
+                if chunk.len() < 16 {
+                    return Err(invalid_data("fmt chunk is too short"));
+                }
+
+                let mut format_tag = read_le_u16(chunk, 0);
+                if format_tag == WAVE_FORMAT_EXTENSIBLE && chunk.len() >= 26 {
+                    // The sub format GUID starts with the real format tag
+                    format_tag = read_le_u16(chunk, 24);
+                }
+
+                let channels = read_le_u16(chunk, 2);
+                let sample_rate = read_le_u32(chunk, 4);
+                let bits_per_sample = read_le_u16(chunk, 14);
+                format = Some((format_tag, channels, sample_rate, bits_per_sample));
+            },
+            b"data" => data = Some(chunk),
+            b"clm " => frame_size = parse_frame_size(chunk),
+            _ => {},
+        }
+
+        // Chunks are padded to an even length
+        offset = start + size + (size % 2);
+    }
+
+    let (format_tag, channels, sample_rate, bits_per_sample) = format.ok_or_else(|| invalid_data("missing fmt chunk"))?;
+    let data = data.ok_or_else(|| invalid_data("missing data chunk"))?;
+
+    let bytes_per_sample = (bits_per_sample as usize + 7) / 8;
+    let block_align = bytes_per_sample * channels as usize;
+    if block_align == 0 {
+        return Err(invalid_data("fmt chunk has no channels or zero bit samples"));
+    }
+
+    let mut samples: Vec<f32> = Vec::with_capacity(data.len() / block_align);
+    for block in data.chunks_exact(block_align) {
+        let mut sum = 0.0;
+        for channel in block.chunks_exact(bytes_per_sample) {
+            sum += decode_sample(channel, format_tag, bits_per_sample)
+                .ok_or_else(|| invalid_data(&format!("unsupported format {} with {} bits per sample", format_tag, bits_per_sample)))?;
+        }
+
+        samples.push(sum / channels as f32);
+    }
+
+    return Ok(WavData { sample_rate, samples, frame_size });
+}
+
+pub fn read_wav<P: AsRef<Path>>(path: P) -> io::Result<WavData> {
+    return parse_wav(&fs::read(path)?);
+}
+
+enum RecorderMessage {
+    Samples(Vec<f32>),
+    Finish,
+}
+
+// Passes samples through to the audio output while bouncing them to a WAV file, so a live
+// session can be captured. Samples are handed to a writer thread in blocks so the audio
+// thread never waits on the disk.
+pub struct WavRecorder<S: Source<Item = f32>> {
+    source: S,
+    sender: Option<Sender<RecorderMessage>>,
+    block: Vec<f32>,
+    // Set by WavRecording when the recording should be finished
+    finishing: Arc<AtomicBool>,
+}
+
+// Finalizes the file from outside the audio thread, which owns the recorder and may never
+// drop it when the process exits
+pub struct WavRecording {
+    sender: Sender<RecorderMessage>,
+    writer_thread: Option<JoinHandle<io::Result<()>>>,
+    finishing: Arc<AtomicBool>,
+}
+
+impl<S: Source<Item = f32>> WavRecorder<S> {
+    pub fn new<P: AsRef<Path>>(source: S, path: P, format: WavFormat, dither: bool) -> io::Result<(WavRecorder<S>, WavRecording)> {
+        let writer = WavWriter::create(path, format, dither)?;
+        let (sender, receiver) = channel();
+
+        let writer_thread = thread::spawn(move || {
+            let result = write_recording(writer, receiver);
+            if let Err(error) = &result {
+                eprintln!("Stopped recording, failed to write the WAV file: {}", error);
+            }
+
+            return result;
+        });
+
+        let finishing = Arc::new(AtomicBool::new(false));
+
+        let recorder = WavRecorder {
+            source,
+            sender: Some(sender.clone()),
+            block: Vec::with_capacity(RECORDER_BLOCK_SIZE),
+            finishing: finishing.clone(),
+        };
+
+        return Ok((recorder, WavRecording { sender, writer_thread: Some(writer_thread), finishing }));
+    }
+
+    fn send_block(&mut self) {
+        let block = std::mem::replace(&mut self.block, Vec::with_capacity(RECORDER_BLOCK_SIZE));
+
+        // The writer thread has finished or failed, there's nothing more to record
+        if let Some(sender) = &self.sender {
+            if sender.send(RecorderMessage::Samples(block)).is_err() {
+                self.sender = None;
+            }
+        }
+    }
+
+    // Hands over the last samples and stops recording, the source keeps playing
+    fn finish(&mut self) {
+        if !self.block.is_empty() {
+            self.send_block();
+        }
+
+        if let Some(sender) = self.sender.take() {
+            let _ = sender.send(RecorderMessage::Finish);
+        }
+    }
+}
+
+// Writes blocks until the recording is finished, a write fails or every sender is gone.
+// A failed write still leaves the header describing the samples written so far.
+fn write_recording(mut writer: WavWriter<BufWriter<File>>, receiver: Receiver<RecorderMessage>) -> io::Result<()> {
+    for message in receiver {
+        match message {
+            RecorderMessage::Samples(samples) => writer.write_samples(&samples)?,
+            RecorderMessage::Finish => break,
+        }
+    }
+
+    return writer.finalize();
+}
+
+impl WavRecording {
+    // The recorder hands over everything played so far on its next frame. If the audio
+    // thread has stopped playing it, the samples it still holds are left out after a timeout.
+    pub fn finish(mut self) -> io::Result<()> {
+        return self.stop();
+    }
+
+    fn stop(&mut self) -> io::Result<()> {
+        let writer_thread = match self.writer_thread.take() {
+            Some(writer_thread) => writer_thread,
+            None => return Ok(()),
+        };
+
+        self.finishing.store(true, Ordering::Relaxed);
+
+        let start = Instant::now();
+        while !writer_thread.is_finished() && start.elapsed() < RECORDER_FINISH_TIMEOUT {
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        let _ = self.sender.send(RecorderMessage::Finish);
+        return writer_thread.join().unwrap_or_else(|_| Err(io::Error::other("WAV writer thread panicked")));
+    }
+}
+
+impl Drop for WavRecording {
+    fn drop(&mut self) {
+        let _ = self.stop();
+    }
+}
+
+impl<S: Source<Item = f32>> Iterator for WavRecorder<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let sample = self.source.next()?;
+
+        if self.sender.is_some() {
+            self.block.push(sample);
+
+            // Only whole frames are finished, so the file ends on its last channel
+            if self.finishing.load(Ordering::Relaxed) && self.block.len() % self.source.channels() as usize == 0 {
+                self.finish();
+            } else if self.block.len() >= RECORDER_BLOCK_SIZE {
+                self.send_block();
+            }
+        }
+
+        return Some(sample);
+    }
+}
+
+// Nothing more can be played once the recorder is gone, so the recording is complete
+impl<S: Source<Item = f32>> Drop for WavRecorder<S> {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+impl<S: Source<Item = f32>> Source for WavRecorder<S> {
+    fn channels(&self) -> u16 {
+        return self.source.channels();
+    }
+
+    fn sample_rate(&self) -> u32 {
+        return self.source.sample_rate();
+    }
+
+    fn current_frame_len(&self) -> Option<usize> {
+        return self.source.current_frame_len();
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        return self.source.total_duration();
+    }
+}
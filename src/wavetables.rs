@@ -1,9 +1,14 @@
 
+use std::io;
 use std::ops::Index;
 use std::f32::consts::PI;
+use std::path::Path;
 
 use lazy_static::lazy_static;
 
+use crate::constants::DEFAULT_PULSE_WIDTH;
+use crate::noise::NoiseGenerator;
+use crate::wav;
 use crate::wavetype::WaveType;
 
 lazy_static! {
@@ -11,6 +16,9 @@ lazy_static! {
 }
 
 const WAVE_TABLE_SAMPLES: usize = 128;
+const NOISE_TABLE_SEED: u32 = 1;
+// Frame size of Serum style wavetables, used when a file doesn't say
+pub const DEFAULT_FRAME_SIZE: usize = 2048;
 
 pub struct WaveTables {
     sin: WaveTable,
@@ -18,6 +26,9 @@ pub struct WaveTables {
     tri: WaveTable,
     square: WaveTable,
     pulse: WaveTable,
+    white_noise: WaveTable,
+    pink_noise: WaveTable,
+    brown_noise: WaveTable,
 }
 
 impl WaveTables {
@@ -28,6 +39,9 @@ impl WaveTables {
             tri: WaveTable::new(WAVE_TABLE_SAMPLES, WaveType::Triangle), 
             square: WaveTable::new(WAVE_TABLE_SAMPLES, WaveType::Square), 
             pulse: WaveTable::new(WAVE_TABLE_SAMPLES, WaveType::Pulse), 
+            white_noise: WaveTable::new(WAVE_TABLE_SAMPLES, WaveType::WhiteNoise), 
+            pink_noise: WaveTable::new(WAVE_TABLE_SAMPLES, WaveType::PinkNoise), 
+            brown_noise: WaveTable::new(WAVE_TABLE_SAMPLES, WaveType::BrownNoise), 
         }
     }
 
@@ -38,14 +52,19 @@ impl WaveTables {
             WaveType::Triangle => &self.tri,
             WaveType::Square => &self.square,
             WaveType::Pulse => &self.pulse,
+            WaveType::WhiteNoise => &self.white_noise,
+            WaveType::PinkNoise => &self.pink_noise,
+            WaveType::BrownNoise => &self.brown_noise,
         };
     }
 }
 
+// One or more single cycle frames stored back to back. The built in tables have a single
+// frame, tables loaded from a WAV file can have many to morph between.
 pub struct WaveTable {
-    wave_type: WaveType,
     wave_table: Vec<f32>,
     pub wave_table_size: usize,
+    frame_count: usize,
 }
 
 //If a mutable value is requested, IndexMut is used instead.
@@ -57,39 +76,138 @@ impl Index<usize> for WaveTable {
 }
 
 impl WaveTable {
+    pub fn get_frame_count(&self) -> usize {
+        return self.frame_count;
+    }
+
+    // Linearly interpolated value at a phase from 0.0 to 1.0. Position from 0.0 to 1.0 sweeps
+    // from the first frame to the last, crossfading between neighbouring frames.
+    pub fn get_linear(&self, phase: f32, position: f32) -> f32 {
+        return self.morph(position, |frame| self.get_frame_linear(frame, phase));
+    }
+
+    // Cubic (Catmull-Rom) interpolated version of get_linear, smoother for small tables at
+    // the cost of two more lookups per frame
+    pub fn get_cubic(&self, phase: f32, position: f32) -> f32 {
+        return self.morph(position, |frame| self.get_frame_cubic(frame, phase));
+    }
+
+    fn morph<F: Fn(usize) -> f32>(&self, position: f32, get_frame_value: F) -> f32 {
+        if self.frame_count == 1 {
+            return get_frame_value(0);
+        }
+
+        let frame_position = position.clamp(0.0, 1.0) * (self.frame_count - 1) as f32;
+        let frame = (frame_position as usize).min(self.frame_count - 2);
+        let fraction = frame_position - frame as f32;
+
+        let a = get_frame_value(frame);
+        if fraction == 0.0 {
+            return a;
+        }
+
+        return a + (get_frame_value(frame + 1) - a) * fraction;
+    }
+
+    fn get_frame_linear(&self, frame: usize, phase: f32) -> f32 {
+        let size = self.wave_table_size;
+        let samples = &self.wave_table[frame * size..(frame + 1) * size];
+
+        let position = phase * size as f32;
+        let index = position as usize;
+        let fraction = position - index as f32;
+
+        let a = samples[index % size];
+        let b = samples[(index + 1) % size];
+
+        return a + (b - a) * fraction;
+    }
+
+    fn get_frame_cubic(&self, frame: usize, phase: f32) -> f32 {
+        let size = self.wave_table_size;
+        let samples = &self.wave_table[frame * size..(frame + 1) * size];
+
+        let position = phase * size as f32;
+        let index = position as usize;
+        let fraction = position - index as f32;
+
+        let y0 = samples[(index + size - 1) % size];
+        let y1 = samples[index % size];
+        let y2 = samples[(index + 1) % size];
+        let y3 = samples[(index + 2) % size];
+
+        let a = -0.5 * y0 + 1.5 * y1 - 1.5 * y2 + 0.5 * y3;
+        let b = y0 - 2.5 * y1 + 2.0 * y2 - 0.5 * y3;
+        let c = -0.5 * y0 + 0.5 * y2;
+
+        return ((a * fraction + b) * fraction + c) * fraction + y1;
+    }
+
+    // Splits samples into frames of frame_size. Anything shorter than a frame is taken as a
+    // single cycle and a partial frame at the end is dropped.
+    pub fn from_samples(mut samples: Vec<f32>, frame_size: usize) -> io::Result<WaveTable> {
+        if samples.is_empty() || frame_size == 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "wavetable has no samples"));
+        }
+
+        if samples.len() <= frame_size {
+            let wave_table_size = samples.len();
+            return Ok(WaveTable { wave_table: samples, wave_table_size, frame_count: 1 });
+        }
+
+        let frame_count = samples.len() / frame_size;
+        samples.truncate(frame_count * frame_size);
+
+        return Ok(WaveTable { wave_table: samples, wave_table_size: frame_size, frame_count });
+    }
+
+    // Loads a single cycle or multi frame wavetable. Without a frame size the one stored in
+    // the file is used, falling back to DEFAULT_FRAME_SIZE.
+    pub fn from_wav_file<P: AsRef<Path>>(path: P, frame_size: Option<usize>) -> io::Result<WaveTable> {
+        let wav = wav::read_wav(path)?;
+        let frame_size = frame_size.or(wav.frame_size).unwrap_or(DEFAULT_FRAME_SIZE);
+
+        return Self::from_samples(wav.samples, frame_size);
+    }
+
     pub fn new(wave_table_size: usize, wave_type: WaveType) -> WaveTable {
         let mut table = WaveTable { 
-            wave_type: wave_type, 
             wave_table: Vec::with_capacity(wave_table_size), 
-            wave_table_size: wave_table_size };
+            wave_table_size: wave_table_size,
+            frame_count: 1 };
 
-        table.populate_wave_table();
+        table.populate_wave_table(wave_type);
         return table;
     }
 
-    fn populate_wave_table(&mut self){
+    fn populate_wave_table(&mut self, wave_type: WaveType){
         self.wave_table.clear();
         let wave_table_size = self.wave_table_size;
         let wave_table = &mut self.wave_table;
+        // Noise tables are only a snapshot for plotting, oscillators generate noise live
+        let mut noise = NoiseGenerator::new(NOISE_TABLE_SEED);
 
         for n in 0..wave_table_size {
             let t = n as f32 / wave_table_size as f32;
 
             let sin_value = (2.0 * PI * t).sin();
 
-            match self.wave_type {
+            match wave_type {
                 WaveType::Sin => wave_table.push(sin_value),
-                WaveType::Saw => wave_table.push(((t + PI) / PI) % 2.0 - 1.0),
+                // Same shape and phase as the oscillator's analytic wave forms
+                WaveType::Saw => wave_table.push(2.0 * t - 1.0),
                 WaveType::Triangle => wave_table.push(sin_value.asin() * (2.0 / PI)),
-                WaveType::Square => wave_table.push(sin_value.signum()),
+                WaveType::Square => wave_table.push(if t < 0.5 { -1.0 } else { 1.0 }),
                 WaveType::Pulse => {
-                    let duty_cycle = 0.2;
-                    if t < duty_cycle {
+                    if t < DEFAULT_PULSE_WIDTH {
                         wave_table.push(1.0)
                     } else {
                         wave_table.push(-1.0)
                     }
                 }
+                WaveType::WhiteNoise => wave_table.push(noise.white()),
+                WaveType::PinkNoise => wave_table.push(noise.pink()),
+                WaveType::BrownNoise => wave_table.push(noise.brown()),
             }
         }
     }
@@ -4,11 +4,171 @@ pub enum WaveType {
     Saw,
     Triangle,
     Square,
-    Pulse
+    Pulse,
+    WhiteNoise,
+    PinkNoise,
+    BrownNoise
 }
 
 impl Default for WaveType {
     fn default() -> Self {
         Self::Sin
     }
-}
\ No newline at end of file
+}
+
+impl WaveType {
+    // Noise isn't periodic so it ignores the frequency, phase and quality
+    pub fn is_noise(&self) -> bool {
+        return matches!(self, WaveType::WhiteNoise | WaveType::PinkNoise | WaveType::BrownNoise);
+    }
+}
+
+// How wave forms are generated. Band limited smooths each discontinuity with PolyBLEP so
+// high notes don't alias, naive is the raw wave form. The wavetable modes read WAVE_TABLES
+// with linear or cubic interpolation, which avoids the trig calls when running many voices.
+#[derive(Debug, PartialEq, Copy, Clone, EnumIter, IntoStaticStr, EnumString, EnumVariantNames)]
+pub enum OscillatorQuality {
+    BandLimited,
+    Naive,
+    WavetableLinear,
+    WavetableCubic
+}
+
+impl Default for OscillatorQuality {
+    fn default() -> Self {
+        Self::BandLimited
+    }
+}
+
+// Where unison voices start in their cycle when a note is pressed. Fixed starts every voice at
+// the same phase, random gives each voice its own seeded phase and free running picks up each
+// voice as if its oscillator had been running since the synth started.
+#[derive(Debug, PartialEq, Copy, Clone, EnumIter, IntoStaticStr, EnumString, EnumVariantNames)]
+pub enum PhaseMode {
+    Fixed,
+    Random,
+    FreeRunning
+}
+
+impl Default for PhaseMode {
+    fn default() -> Self {
+        Self::Fixed
+    }
+}
+
+// How an oscillator is modulated by another. Hard sync restarts its cycle with the other
+// oscillator's, ring modulation multiplies the two and amplitude modulation scales its level
+// by the other oscillator moved into 0.0 to 1.0.
+#[derive(Debug, PartialEq, Copy, Clone, EnumIter, IntoStaticStr, EnumString, EnumVariantNames)]
+pub enum OscModulation {
+    Off,
+    HardSync,
+    RingMod,
+    AmplitudeMod
+}
+
+impl Default for OscModulation {
+    fn default() -> Self {
+        Self::Off
+    }
+}
+
+// Oscillators wired as FM operators, written as modulators -> carrier. Only carriers are heard
+// and modulators always have a higher number than what they modulate.
+#[derive(Debug, PartialEq, Copy, Clone, EnumIter, IntoStaticStr, EnumString, EnumVariantNames)]
+pub enum FmAlgorithm {
+    // Every oscillator is heard, nothing is frequency modulated
+    Off,
+    // 3 -> 2 -> 1
+    Stack,
+    // 2 + 3 -> 1
+    Branch,
+    // 3 -> 1 and 3 -> 2
+    Split,
+    // 2 -> 1 with 3 heard on its own
+    Pair
+}
+
+impl Default for FmAlgorithm {
+    fn default() -> Self {
+        Self::Off
+    }
+}
+
+impl FmAlgorithm {
+    // Oscillator numbers modulating the phase of `osc_num`
+    pub fn get_modulators(&self, osc_num: usize) -> &'static [usize] {
+        return match (self, osc_num) {
+            (FmAlgorithm::Stack, 0) => &[1],
+            (FmAlgorithm::Stack, 1) => &[2],
+            (FmAlgorithm::Branch, 0) => &[1, 2],
+            (FmAlgorithm::Split, 0 | 1) => &[2],
+            (FmAlgorithm::Pair, 0) => &[1],
+            _ => &[],
+        };
+    }
+
+    pub fn is_carrier(&self, osc_num: usize) -> bool {
+        return match self {
+            FmAlgorithm::Off => true,
+            FmAlgorithm::Stack | FmAlgorithm::Branch => osc_num == 0,
+            FmAlgorithm::Split => osc_num != 2,
+            FmAlgorithm::Pair => osc_num != 1,
+        };
+    }
+}
+
+// Response of each voice's filter, off passes the oscillators through untouched
+#[derive(Debug, PartialEq, Copy, Clone, EnumIter, IntoStaticStr, EnumString, EnumVariantNames)]
+pub enum FilterMode {
+    Off,
+    LowPass,
+    HighPass,
+    BandPass,
+    Notch,
+    // 24dB/oct low pass with drive
+    Ladder
+}
+
+impl Default for FilterMode {
+    fn default() -> Self {
+        Self::Off
+    }
+}
+
+// What a modulation envelope is added to
+#[derive(Debug, PartialEq, Copy, Clone, EnumIter, IntoStaticStr, EnumString, EnumVariantNames)]
+pub enum EnvelopeDestination {
+    FilterCutoff,
+    Pitch,
+    PulseWidth,
+    FmIndex
+}
+
+impl Default for EnvelopeDestination {
+    fn default() -> Self {
+        Self::FilterCutoff
+    }
+}
+
+// How an envelope stage moves between its levels. Exponential moves quickly at first and
+// eases into the next level like a charging capacitor, logarithmic starts slowly and speeds up.
+#[derive(Debug, PartialEq, Copy, Clone, EnumIter, IntoStaticStr, EnumString, EnumVariantNames)]
+pub enum EnvelopeCurve {
+    Linear,
+    Exponential,
+    Logarithmic
+}
+
+impl Default for EnvelopeCurve {
+    fn default() -> Self {
+        Self::Linear
+    }
+}
+
+#[derive(Debug, PartialEq, Copy, Clone, EnumCount, EnumIter, IntoStaticStr, EnumString, EnumVariantNames)]
+pub enum EnvelopeStage {
+    Attack,
+    Decay,
+    Release
+}
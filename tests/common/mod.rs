@@ -0,0 +1,38 @@
+// Each test file only uses some of these helpers
+#![allow(dead_code)]
+
+use oxidizer::constants::{SAMPLE_RATE, NUM_CHANNELS};
+use oxidizer::synthesizer::{Synthesizer, SynthEvent, TimedSynthEvent};
+
+// Power at a frequency in Hz using the Goertzel algorithm. A frequency that fits a whole
+// number of cycles into the samples lands exactly on a bin.
+pub fn power_at(samples: &[f32], frequency: f32) -> f64 {
+    let coefficient = 2.0 * (2.0 * std::f64::consts::PI * frequency as f64 / SAMPLE_RATE as f64).cos();
+    let (mut s1, mut s2) = (0.0, 0.0);
+
+    for sample in samples {
+        let s0 = *sample as f64 + coefficient * s1 - s2;
+        s2 = s1;
+        s1 = s0;
+    }
+
+    return s1 * s1 + s2 * s2 - coefficient * s1 * s2;
+}
+
+// Left channel of an offline render, starting `skip` seconds in
+pub fn render_events_left(events: Vec<TimedSynthEvent>, duration: f32, skip: f32) -> Vec<f32> {
+    return Synthesizer::new_offline()
+        .render(events, duration)
+        .into_iter()
+        .step_by(NUM_CHANNELS as usize)
+        .skip((skip * SAMPLE_RATE) as usize)
+        .collect();
+}
+
+// Applies the setup events then holds the note at full velocity for the whole render
+pub fn render_left(setup_events: Vec<SynthEvent>, note: i32, duration: f32, skip: f32) -> Vec<f32> {
+    let mut events: Vec<TimedSynthEvent> = setup_events.into_iter().map(|event| TimedSynthEvent::new(0.0, event)).collect();
+    events.push(TimedSynthEvent::new(0.0, SynthEvent::NotePress(note, 1.0)));
+
+    return render_events_left(events, duration, skip);
+}
@@ -0,0 +1,106 @@
+use oxidizer::constants::EnvelopeShape;
+use oxidizer::envelope::{EnvelopeADSR, DEFAULT_START_AMPLITUDE, DEFAULT_SUSTAIN_AMPLITUDE};
+use oxidizer::wavetype::{EnvelopeCurve, EnvelopeStage};
+
+// One second for each stage from a peak of 1.0 to a sustain of 0.5
+fn envelope_with(stage: EnvelopeStage, shape: EnvelopeShape) -> EnvelopeADSR {
+    let mut envelope = EnvelopeADSR::with_levels(1.0, 0.5);
+    envelope.set_attack_time(1.0);
+    envelope.set_decay_time(1.0);
+    envelope.set_release_time(1.0);
+    envelope.set_shape(stage, shape);
+
+    return envelope;
+}
+
+// Level halfway through the stage
+fn midpoint(stage: EnvelopeStage, curve: EnvelopeCurve, tension: f32) -> f32 {
+    let envelope = envelope_with(stage, EnvelopeShape { curve, tension });
+
+    return match stage {
+        EnvelopeStage::Attack => envelope.get_amplitude(0.5, 0.0, 0.0, true, 1.0),
+        EnvelopeStage::Decay => envelope.get_amplitude(1.5, 0.0, 0.0, true, 1.0),
+        EnvelopeStage::Release => envelope.get_amplitude(3.5, 0.0, 3.0, false, 1.0),
+    };
+}
+
+#[test]
+fn curves_bend_each_stage_between_the_same_levels(){
+    for stage in [EnvelopeStage::Attack, EnvelopeStage::Decay, EnvelopeStage::Release] {
+        let linear = midpoint(stage, EnvelopeCurve::Linear, 0.5);
+        let exponential = midpoint(stage, EnvelopeCurve::Exponential, 0.5);
+        let logarithmic = midpoint(stage, EnvelopeCurve::Logarithmic, 0.5);
+        let steeper = midpoint(stage, EnvelopeCurve::Exponential, 1.0);
+
+        // The attack rises while the decay and release fall
+        let towards = |level: f32| if stage == EnvelopeStage::Attack { level } else { -level };
+
+        assert!(towards(exponential) > towards(linear), "{:?} exponential should move faster than linear at first, {} against {}", stage, exponential, linear);
+        assert!(towards(logarithmic) < towards(linear), "{:?} logarithmic should move slower than linear at first, {} against {}", stage, logarithmic, linear);
+        assert!(towards(steeper) > towards(exponential), "{:?} more tension should bend further, {} against {}", stage, steeper, exponential);
+        assert!((midpoint(stage, EnvelopeCurve::Exponential, 0.0) - linear).abs() < 1e-4, "{:?} with no tension should be linear", stage);
+    }
+
+    // Every curve still starts and finishes on the same levels
+    for curve in [EnvelopeCurve::Linear, EnvelopeCurve::Exponential, EnvelopeCurve::Logarithmic] {
+        let mut envelope = EnvelopeADSR::with_levels(1.0, 0.5);
+        envelope.set_attack_time(0.1);
+        envelope.set_release_time(1.0);
+        for stage in [EnvelopeStage::Attack, EnvelopeStage::Decay, EnvelopeStage::Release] {
+            envelope.set_shape(stage, EnvelopeShape { curve, tension: 1.0 });
+        }
+
+        assert!((envelope.get_amplitude(0.1, 0.0, 0.0, true, 1.0) - 1.0).abs() < 1e-4, "{:?} attack should peak at the start amplitude", curve);
+        // The decay ends 1.1s after the press and should meet the sustain without a step. At
+        // full tension no curve moves more than 0.5 x 10 per second, so 1ms steps stay under 0.01.
+        let levels: Vec<f32> = (100..=1200).map(|ms| envelope.get_amplitude(ms as f32 / 1000.0, 0.0, 0.0, true, 1.0)).collect();
+        for pair in levels.windows(2) {
+            assert!((pair[0] - pair[1]).abs() < 0.01, "{:?} decay should be continuous with the sustain but stepped from {} to {}", curve, pair[0], pair[1]);
+        }
+        assert!((levels[1000] - 0.5).abs() < 1e-4, "{:?} decay should finish on the sustain but is at {}", curve, levels[1000]);
+        assert!((envelope.get_amplitude(2.0, 0.0, 0.0, true, 1.0) - 0.5).abs() < 1e-4, "{:?} should hold the sustain", curve);
+        assert!((envelope.get_amplitude(3.0, 0.0, 3.0, false, 1.0) - 0.5).abs() < 1e-4, "{:?} release should start from the sustain", curve);
+        assert_eq!(envelope.get_amplitude(4.0, 0.0, 3.0, false, 1.0), 0.0, "{:?} release should finish silent", curve);
+    }
+}
+
+#[test]
+fn sustain_amplitude_can_be_set(){
+    let mut envelope = EnvelopeADSR::new();
+    assert_eq!(envelope.get_amplitude(2.0, 0.0, 0.0, true, 1.0), DEFAULT_SUSTAIN_AMPLITUDE, "Default envelope should sustain at the default level");
+
+    envelope.set_sustain_amplitude(DEFAULT_START_AMPLITUDE / 2.0);
+    assert_eq!(envelope.get_amplitude(2.0, 0.0, 0.0, true, 1.0), DEFAULT_START_AMPLITUDE / 2.0, "Envelope should sustain at the new level");
+    let released = envelope.get_amplitude(2.05, 0.0, 2.0, false, 1.0);
+    assert!((released - DEFAULT_START_AMPLITUDE / 4.0).abs() < 1e-6, "Release should fall from the new sustain level but is at {}", released);
+}
+
+#[test]
+fn sustain_amplitude_stays_below_the_peak(){
+    let mut envelope = EnvelopeADSR::new();
+
+    envelope.set_sustain_amplitude(1.0);
+    assert_eq!(envelope.get_amplitude(2.0, 0.0, 0.0, true, 1.0), DEFAULT_START_AMPLITUDE, "Sustain above the peak should be held at the peak");
+
+    envelope.set_sustain_amplitude(-1.0);
+    assert_eq!(envelope.get_amplitude(2.0, 0.0, 0.0, true, 1.0), 0.0, "Negative sustain should be silent");
+}
+
+#[test]
+fn decay_runs_its_full_time_after_the_attack(){
+    // Attack longer than the decay, which used to skip the decay altogether
+    let mut envelope = EnvelopeADSR::with_levels(1.0, 0.5);
+    envelope.set_attack_time(0.5);
+    envelope.set_decay_time(0.2);
+
+    let level = |time: f32| envelope.get_amplitude(time, 0.0, 0.0, true, 1.0);
+    assert!((level(0.6) - 0.75).abs() < 1e-4, "Halfway through the decay should be halfway to the sustain, not {}", level(0.6));
+    assert!((level(0.7) - 0.5).abs() < 1e-4, "Decay should reach the sustain at the end of its time, not {}", level(0.7));
+
+    // Steps of 1ms from the peak to past the end of the decay, the level falls 2.5 per second
+    let levels: Vec<f32> = (500..=800).map(|ms| level(ms as f32 / 1000.0)).collect();
+    for pair in levels.windows(2) {
+        let step = pair[0] - pair[1];
+        assert!(step >= -1e-6 && step < 0.003, "Decay should fall smoothly to the sustain but stepped from {} to {}", pair[0], pair[1]);
+    }
+}
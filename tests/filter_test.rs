@@ -0,0 +1,167 @@
+use std::f64::consts::PI;
+
+use oxidizer::constants::SAMPLE_RATE;
+use oxidizer::filter::{StateVariableFilter, LadderFilter, MAX_CUTOFF};
+use oxidizer::random::Random;
+use oxidizer::wavetype::FilterMode;
+
+const CUTOFF: f32 = 1000.0;
+const RESONANCE: f32 = 0.5;
+
+// Amplitude of a sine after the filter relative to its own, measured over the second half of
+// a second once the filter has settled. Even frequencies fit a whole number of cycles into the half.
+fn measured_gain(filter: &mut dyn FnMut(f32) -> f32, amplitude: f64, frequency: u32) -> f64 {
+    let num_samples = SAMPLE_RATE as usize;
+    let (mut in_phase, mut quadrature) = (0.0, 0.0);
+
+    for i in 0..num_samples {
+        let phase = 2.0 * PI * frequency as f64 * i as f64 / SAMPLE_RATE as f64;
+        let output = filter((amplitude * phase.sin()) as f32) as f64;
+
+        if i >= num_samples / 2 {
+            in_phase += output * phase.sin();
+            quadrature += output * phase.cos();
+        }
+    }
+
+    return 2.0 * (in_phase * in_phase + quadrature * quadrature).sqrt() / (num_samples / 2) as f64 / amplitude;
+}
+
+fn measured_state_variable_gain(mode: FilterMode, frequency: u32) -> f64 {
+    let mut filter = StateVariableFilter::new(mode);
+    filter.set_cutoff(CUTOFF, RESONANCE);
+
+    return measured_gain(&mut |input| filter.process(input, 0), 1.0, frequency);
+}
+
+// How far the bilinear transform squeezes a frequency, relative to the cutoff
+fn warped(frequency: u32) -> f64 {
+    let warp = |f: f64| (PI * f / SAMPLE_RATE as f64).tan();
+    return warp(frequency as f64) / warp(CUTOFF as f64);
+}
+
+// The analog response with the bilinear transform's frequency warping
+fn analytic_gain(mode: FilterMode, frequency: u32) -> f64 {
+    let w = warped(frequency);
+    let k = 2.0 - 1.95 * RESONANCE as f64;
+    let denominator = ((1.0 - w * w).powi(2) + (k * w).powi(2)).sqrt();
+
+    return match mode {
+        FilterMode::Off | FilterMode::Ladder => 1.0,
+        FilterMode::LowPass => 1.0 / denominator,
+        FilterMode::HighPass => w * w / denominator,
+        FilterMode::BandPass => w / denominator,
+        FilterMode::Notch => (1.0 - w * w).abs() / denominator,
+    };
+}
+
+#[test]
+fn filter_modes_match_the_analytic_response(){
+    for mode in [FilterMode::Off, FilterMode::LowPass, FilterMode::HighPass, FilterMode::BandPass, FilterMode::Notch] {
+        for frequency in [100, 500, 800, 1000, 1200, 2000, 8000, 16000] {
+            let measured = measured_state_variable_gain(mode, frequency);
+            let expected = analytic_gain(mode, frequency);
+
+            // Compare in dB, except right in the notch where both are close to nothing
+            if expected > 0.001 {
+                let error = 20.0 * (measured / expected).log10();
+                assert!(error.abs() < 0.1, "{:?} at {}Hz should have a gain of {:.4} but has {:.4}", mode, frequency, expected, measured);
+            } else {
+                assert!(measured < 0.002, "{:?} at {}Hz should be silent but has a gain of {:.4}", mode, frequency, measured);
+            }
+        }
+    }
+}
+
+#[test]
+fn filter_stays_stable_with_full_resonance_and_a_jumping_cutoff(){
+    let mut random = Random::new(99);
+
+    for mode in [FilterMode::LowPass, FilterMode::HighPass, FilterMode::BandPass, FilterMode::Notch] {
+        let mut filter = StateVariableFilter::new(mode);
+
+        for i in 0..SAMPLE_RATE as usize {
+            // A new cutoff anywhere from below 20Hz to above Nyquist every 64 samples
+            if i % 64 == 0 {
+                filter.set_cutoff(random.next_f32() * SAMPLE_RATE, 1.0);
+            }
+
+            let [left, right] = filter.process_frame([random.next_bipolar(), 1.0]);
+            assert!(left.is_finite() && left.abs() < 100.0, "{:?} blew up to {} on noise", mode, left);
+            assert!(right.is_finite() && right.abs() < 100.0, "{:?} blew up to {} on a constant input", mode, right);
+        }
+    }
+}
+
+// Four one pole low passes 1 / (1 + s) in a loop with feedback k, at the warped frequency
+fn analytic_ladder_gain(resonance: f32, frequency: u32) -> f64 {
+    let w = warped(frequency);
+    let k = 4.2 * resonance as f64;
+
+    // (1 + jw)^4 expanded into its real and imaginary parts
+    let real = 1.0 - 6.0 * w.powi(2) + w.powi(4);
+    let imaginary = 4.0 * w - 4.0 * w.powi(3);
+
+    return 1.0 / ((real + k).powi(2) + imaginary.powi(2)).sqrt();
+}
+
+#[test]
+fn ladder_matches_the_analytic_response(){
+    for resonance in [0.0, 0.5, 0.9] {
+        for frequency in [100, 500, 800, 1000, 1200, 2000, 4000, 8000, 16000] {
+            let mut filter = LadderFilter::new();
+            filter.set_cutoff(CUTOFF, resonance);
+
+            // Quiet enough that the saturation stays linear
+            let measured = measured_gain(&mut |input| filter.process(input, 0), 0.001, frequency);
+            let expected = analytic_ladder_gain(resonance, frequency);
+
+            let error = 20.0 * (measured / expected).log10();
+            assert!(error.abs() < 0.1, "Ladder with resonance {} at {}Hz should have a gain of {:.6} but has {:.6}", resonance, frequency, expected, measured);
+        }
+    }
+}
+
+#[test]
+fn ladder_self_oscillates_at_its_cutoff(){
+    let mut filter = LadderFilter::new();
+    filter.set_cutoff(CUTOFF, 1.0);
+    filter.set_drive(12.0);
+
+    // A single click starts it ringing and after that it should carry on by itself
+    let mut samples: Vec<f32> = (0..SAMPLE_RATE as usize * 2).map(|i| filter.process(if i == 0 { 1.0 } else { 0.0 }, 0)).collect();
+    let last_second = samples.split_off(SAMPLE_RATE as usize);
+
+    let peak = last_second.iter().fold(0.0, |peak: f32, sample| peak.max(sample.abs()));
+    let crossings = last_second.windows(2).filter(|pair| pair[0] < 0.0 && pair[1] >= 0.0).count() as f32;
+
+    assert!(peak > 0.01 && peak < 2.0, "Self oscillation should settle at a steady level but peaks at {}", peak);
+    assert!((crossings - CUTOFF).abs() < CUTOFF * 0.02, "Self oscillation should be at the cutoff but crossed zero {} times", crossings);
+}
+
+#[test]
+fn ladder_stays_stable_across_the_cutoff_range(){
+    let mut random = Random::new(99);
+    let mut filter = LadderFilter::new();
+    filter.set_drive(24.0);
+
+    for i in 0..SAMPLE_RATE as usize {
+        if i % 64 == 0 {
+            filter.set_cutoff(random.next_f32() * SAMPLE_RATE, random.next_f32());
+        }
+
+        let [left, right] = filter.process_frame([random.next_bipolar(), 1.0]);
+        assert!(left.is_finite() && left.abs() < 10.0, "Ladder blew up to {} on noise", left);
+        assert!(right.is_finite() && right.abs() < 10.0, "Ladder blew up to {} on a constant input", right);
+    }
+
+    // Right up against Nyquist as well
+    for cutoff in [MAX_CUTOFF, SAMPLE_RATE] {
+        filter.set_cutoff(cutoff, 1.0);
+
+        for _ in 0..SAMPLE_RATE as usize {
+            let sample = filter.process(random.next_bipolar(), 0);
+            assert!(sample.is_finite() && sample.abs() < 10.0, "Ladder blew up to {} with a {}Hz cutoff", sample, cutoff);
+        }
+    }
+}
@@ -0,0 +1,166 @@
+use oxidizer::constants::SAMPLE_RATE;
+use oxidizer::midi_file::*;
+use oxidizer::synthesizer::{SynthEvent, TimedSynthEvent};
+
+fn variable_length(mut value: u32) -> Vec<u8> {
+    let mut bytes = vec![(value & 0x7F) as u8];
+    value >>= 7;
+
+    while value > 0 {
+        bytes.insert(0, (value & 0x7F) as u8 | 0x80);
+        value >>= 7;
+    }
+
+    return bytes;
+}
+
+fn smf(format: u16, division: u16, tracks: &[Vec<u8>]) -> Vec<u8> {
+    let mut bytes: Vec<u8> = Vec::new();
+    bytes.extend(b"MThd");
+    bytes.extend(6u32.to_be_bytes());
+    bytes.extend(format.to_be_bytes());
+    bytes.extend((tracks.len() as u16).to_be_bytes());
+    bytes.extend(division.to_be_bytes());
+
+    for track in tracks {
+        bytes.extend(b"MTrk");
+        bytes.extend((track.len() as u32).to_be_bytes());
+        bytes.extend(track);
+    }
+
+    return bytes;
+}
+
+fn event(delta: u32, data: &[u8]) -> Vec<u8> {
+    return [variable_length(delta), data.to_vec()].concat();
+}
+
+fn end_of_track() -> Vec<u8> {
+    return event(0, &[0xFF, 0x2F, 0x00]);
+}
+
+fn note_at(events: &[TimedSynthEvent], i: usize) -> (u64, bool, i32) {
+    return match events[i].event {
+        SynthEvent::NotePress(note, _) => (events[i].sample_offset, true, note),
+        SynthEvent::NoteRelease(note) => (events[i].sample_offset, false, note),
+        _ => panic!("Expected a note event"),
+    };
+}
+
+#[test]
+fn type_0_with_running_status(){
+    // A4 for one beat at the default 120bpm, then C5 using running status
+    let track = [
+        event(0, &[0x90, 69, 100]),
+        event(480, &[69, 0]),
+        event(0, &[72, 100]),
+        event(480, &[0x80, 72, 64]),
+        end_of_track(),
+    ].concat();
+
+    let midi_file = MidiFile::parse(&smf(0, 480, &[track])).unwrap();
+    let events = midi_file.to_synth_events();
+    let half_second = (SAMPLE_RATE / 2.0) as u64;
+
+    assert_eq!(events.len(), 4);
+    assert_eq!(note_at(&events, 0), (0, true, 69));
+    assert_eq!(note_at(&events, 1), (half_second, false, 69), "Note on with zero velocity should release");
+    assert_eq!(note_at(&events, 2), (half_second, true, 72));
+    assert_eq!(note_at(&events, 3), (half_second * 2, false, 72));
+}
+
+#[test]
+fn type_1_tempo_map_applies_to_all_tracks(){
+    // 4/4 at 120bpm, switching to 60bpm after one beat
+    let conductor = [
+        event(0, &[0xFF, 0x58, 0x04, 4, 2, 24, 8]),
+        event(0, &[0xFF, 0x51, 0x03, 0x07, 0xA1, 0x20]),
+        event(480, &[0xFF, 0x51, 0x03, 0x0F, 0x42, 0x40]),
+        end_of_track(),
+    ].concat();
+
+    let notes = [
+        event(480, &[0x91, 60, 90]),
+        event(0, &[0xF0, 0x03, 0x7E, 0x7F, 0xF7]),
+        event(480, &[0x81, 60, 0]),
+        end_of_track(),
+    ].concat();
+
+    let midi_file = MidiFile::parse(&smf(1, 480, &[conductor, notes])).unwrap();
+    let events = midi_file.to_synth_events();
+
+    assert_eq!(midi_file.tracks.len(), 2);
+    assert_eq!(midi_file.get_time_signatures()[0].1.numerator, 4);
+    assert_eq!(midi_file.get_time_signatures()[0].1.denominator, 4);
+
+    assert_eq!(note_at(&events, 0).0, (SAMPLE_RATE * 0.5) as u64, "First beat is at 120bpm");
+    assert_eq!(note_at(&events, 1).0, (SAMPLE_RATE * 1.5) as u64, "Second beat is at 60bpm");
+    assert_eq!(midi_file.get_duration(), 1.5);
+}
+
+#[test]
+fn malformed_files_are_rejected(){
+    assert!(matches!(MidiFile::parse(b"RIFF0000"), Err(MidiFileError::NotAMidiFile)));
+    assert!(matches!(MidiFile::parse(&smf(2, 480, &[])), Err(MidiFileError::UnsupportedFormat(2))));
+
+    // The track chunk starts after the 14 byte header and its own 8 byte chunk header
+    let truncated = smf(0, 480, &[event(0, &[0x90, 60, 100])]);
+    assert!(matches!(MidiFile::parse(&truncated[..truncated.len() - 1]), Err(MidiFileError::UnexpectedEndOfData(22))), "Should fail where the cut off chunk starts");
+
+    let cut_off_event = smf(0, 480, &[event(0, &[0x90, 60])]);
+    assert!(matches!(MidiFile::parse(&cut_off_event), Err(MidiFileError::UnexpectedEndOfData(25))), "Should fail at the missing velocity");
+
+    let no_status = smf(0, 480, &[event(0, &[60, 100])]);
+    assert!(matches!(MidiFile::parse(&no_status), Err(MidiFileError::MissingRunningStatus(23))), "Should fail at the data byte");
+
+    assert!(matches!(MidiFile::parse(&smf(0, 0, &[])), Err(MidiFileError::InvalidTimeDivision(0))));
+    assert!(matches!(MidiFile::parse(&smf(0, 0xE700, &[])), Err(MidiFileError::InvalidTimeDivision(0xE700))), "SMPTE timing needs ticks per frame");
+}
+
+#[test]
+fn parser_skips_what_it_does_not_play(){
+    // Program change, pitch bend, a text meta event and a long delta between the notes
+    let track = [
+        event(0, &[0xC0, 5]),
+        event(0, &[0xE0, 0x00, 0x40]),
+        event(0, &[0xFF, 0x01, 0x03, b'a', b'b', b'c']),
+        event(0, &[0x90, 60, 100]),
+        event(0x0FFFFFFF, &[0x80, 60, 0]),
+        end_of_track(),
+        // Anything after the end of the track is ignored
+        event(0, &[0x90, 62, 100]),
+    ].concat();
+
+    let mut bytes = smf(0, 480, &[track]);
+    // A longer header and an unknown chunk before the track should both be stepped over
+    bytes[7] = 8;
+    bytes.splice(14..14, [0, 0]);
+    bytes.splice(16..16, [b"XFIH".as_slice(), &2u32.to_be_bytes(), &[1, 2]].concat());
+
+    let midi_file = MidiFile::parse(&bytes).unwrap();
+
+    assert_eq!(midi_file.tracks.len(), 1);
+    assert_eq!(midi_file.tracks[0], vec![
+        MidiTrackEvent { tick: 0, kind: MidiEventKind::NoteOn { channel: 0, note: 60, velocity: 100 } },
+        MidiTrackEvent { tick: 0x0FFFFFFF, kind: MidiEventKind::NoteOff { channel: 0, note: 60, velocity: 0 } },
+        MidiTrackEvent { tick: 0x0FFFFFFF, kind: MidiEventKind::EndOfTrack },
+    ], "Only the notes and the end of the track should be kept");
+}
+
+#[test]
+fn smpte_timing_ignores_tempo(){
+    // 25 frames per second with 40 ticks each is a millisecond per tick
+    let track = [
+        event(0, &[0xFF, 0x51, 0x03, 0x0F, 0x42, 0x40]),
+        event(500, &[0x90, 60, 100]),
+        event(1000, &[0x80, 60, 0]),
+        end_of_track(),
+    ].concat();
+
+    let midi_file = MidiFile::parse(&smf(0, 0xE728, &[track])).unwrap();
+    let events = midi_file.to_synth_events();
+
+    assert_eq!(midi_file.division, TimeDivision::Smpte { frames_per_second: 25, ticks_per_frame: 40 });
+    assert_eq!(note_at(&events, 0).0, (SAMPLE_RATE * 0.5) as u64);
+    assert_eq!(note_at(&events, 1).0, (SAMPLE_RATE * 1.5) as u64);
+}
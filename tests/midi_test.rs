@@ -0,0 +1,77 @@
+mod common;
+
+use std::io::Cursor;
+use std::sync::mpsc::{Sender, Receiver, channel};
+
+use oxidizer::constants::SAMPLE_RATE;
+use oxidizer::midi::{MidiDecoder, MidiMessage, send_synth_events};
+use oxidizer::synthesizer::{Synthesizer, SynthEvent, TimedSynthEvent};
+
+use common::render_events_left;
+
+#[test]
+fn decodes_running_status_and_skips_system_messages(){
+    let mut decoder = MidiDecoder::new();
+
+    let bytes = [
+        0x90, 60, 100,
+        64, 90,                         // running status note on
+        0xF8,                           // clock in the middle of a stream
+        0xF0, 0x7E, 0x7F, 0x09, 0xF7,   // sysex
+        0x40,                           // data byte with no status after sysex is dropped
+        0x80, 60, 0xFE, 0,              // active sensing inside a message
+        0xB2, 64, 127,
+        0xC2, 5,
+        0xD2, 127,
+        0xE2, 0x00, 0x40,
+        0xE2, 0x7F, 0x7F,
+        0xF2, 0x10, 0x20,               // song position pointer
+        0x30, 0x40,
+    ];
+
+    let messages = decoder.decode(&bytes);
+
+    assert_eq!(messages, vec![
+        MidiMessage::NoteOn { channel: 0, note: 60, velocity: 100 },
+        MidiMessage::NoteOn { channel: 0, note: 64, velocity: 90 },
+        MidiMessage::NoteOff { channel: 0, note: 60, velocity: 0 },
+        MidiMessage::ControlChange { channel: 2, controller: 64, value: 127 },
+        MidiMessage::ProgramChange { channel: 2, program: 5 },
+        MidiMessage::ChannelPressure { channel: 2, pressure: 127 },
+        MidiMessage::PitchBend { channel: 2, value: 0 },
+        MidiMessage::PitchBend { channel: 2, value: 8191 },
+    ]);
+}
+
+#[test]
+fn note_on_with_no_velocity_releases_the_note(){
+    let mut decoder = MidiDecoder::new();
+    let mut events: Vec<TimedSynthEvent> = Vec::new();
+
+    for (time, bytes) in [(0.0, [0x90, 57, 100]), (0.2, [0x90, 57, 0])] {
+        for message in decoder.decode(&bytes) {
+            events.extend(message.to_synth_event().map(|event| TimedSynthEvent::new(time, event)));
+        }
+    }
+
+    let left = render_events_left(events, 0.5, 0.0);
+    assert!(left[..(0.2 * SAMPLE_RATE) as usize].iter().any(|sample| sample.abs() > 0.05), "Note on should play the note");
+    assert!(left[(0.35 * SAMPLE_RATE) as usize..].iter().all(|sample| *sample == 0.0), "Note on with no velocity should release the note");
+}
+
+#[test]
+fn sends_a_byte_stream_to_the_synth(){
+    let (synth_sender, synth_receiver): (Sender<SynthEvent>, Receiver<SynthEvent>) = channel();
+
+    // Pedal down, then a note pressed and released under it
+    let bytes = vec![0xB0, 64, 127, 0x90, 57, 100, 57, 0];
+    assert!(send_synth_events(Cursor::new(bytes), &synth_sender).is_ok(), "The end of the stream should end sending");
+
+    let mut synth = Synthesizer::new(synth_receiver);
+    let left: Vec<f32> = (0..(0.5 * SAMPLE_RATE) as usize).map(|_| synth.get_synth_frame()[0]).collect();
+    assert!(left[(0.4 * SAMPLE_RATE) as usize..].iter().any(|sample| sample.abs() > 0.05), "The streamed pedal should hold the streamed note");
+
+    // Stops quietly once the synth has gone away
+    drop(synth);
+    assert!(send_synth_events(Cursor::new(vec![0x90, 57, 100]), &synth_sender).is_ok(), "Sending to a dropped synth should stop without an error");
+}
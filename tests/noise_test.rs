@@ -0,0 +1,88 @@
+mod common;
+
+use oxidizer::constants::*;
+use oxidizer::noise::NoiseGenerator;
+use oxidizer::synthesizer::{Synthesizer, SynthEvent, TimedSynthEvent};
+use oxidizer::wavetype::WaveType;
+
+use common::{power_at, render_left, render_events_left};
+
+// Average power over a band of 1Hz bins, a single bin of noise varies too much to compare
+fn band_power(samples: &[f32], low: u32, high: u32) -> f64 {
+    let total: f64 = (low..high).map(|frequency| power_at(samples, frequency as f32)).sum();
+    return total / (high - low) as f64;
+}
+
+// Power 4 octaves up relative to 200Hz, in dB
+fn four_octave_slope(next: &mut dyn FnMut() -> f32) -> f64 {
+    let samples: Vec<f32> = (0..SAMPLE_RATE as usize).map(|_| next()).collect();
+    let low = band_power(&samples, 150, 250);
+    let high = band_power(&samples, 3150, 3250);
+
+    return 10.0 * (high / low).log10();
+}
+
+#[test]
+fn noise_colours_have_their_spectral_slopes(){
+    let mut noise = NoiseGenerator::new(1234);
+
+    let white = four_octave_slope(&mut || noise.white());
+    let pink = four_octave_slope(&mut || noise.pink());
+    let brown = four_octave_slope(&mut || noise.brown());
+
+    assert!(white.abs() < 3.0, "White noise should be flat but falls {:.1}dB over 4 octaves", white);
+    assert!((pink + 12.0).abs() < 3.0, "Pink noise should fall 12dB over 4 octaves but falls {:.1}dB", pink);
+    assert!((brown + 24.0).abs() < 3.0, "Brown noise should fall 24dB over 4 octaves but falls {:.1}dB", brown);
+}
+
+#[test]
+fn noise_renders_are_reproducible(){
+    let render = || {
+        let mut params = SoundGenOscParams::create_default_array();
+        params[0].wave_type = WaveType::PinkNoise;
+        params[0].unisons = 3;
+
+        let events = vec![
+            TimedSynthEvent::new(0.0, SynthEvent::ChangeSoundGenOscParams(params[0].clone())),
+            TimedSynthEvent::new(0.0, SynthEvent::NotePress(60, 1.0)),
+        ];
+
+        return Synthesizer::new_offline().render(events, 0.2);
+    };
+
+    let first = render();
+    assert!(first.iter().any(|sample| sample.abs() > 0.01), "Noise note should be audible");
+    assert_eq!(first, render(), "Noise should be the same on every render");
+}
+
+fn rms(samples: &[f32]) -> f32 {
+    return (samples.iter().map(|sample| sample * sample).sum::<f32>() / samples.len() as f32).sqrt();
+}
+
+#[test]
+fn noise_voices_are_independent(){
+    let mut params = SoundGenOscParams::create_default_array();
+    params[0].wave_type = WaveType::WhiteNoise;
+
+    // Each press of the same key plays different noise
+    let events = vec![
+        TimedSynthEvent::new(0.0, SynthEvent::ChangeSoundGenOscParams(params[0].clone())),
+        TimedSynthEvent::new(0.0, SynthEvent::NotePress(60, 1.0)),
+        TimedSynthEvent::new(0.2, SynthEvent::NoteRelease(60)),
+        TimedSynthEvent::new(0.5, SynthEvent::NotePress(60, 1.0)),
+    ];
+    let left = render_events_left(events, 0.7, 0.0);
+    let first_press = &left[..4410];
+    let second_press = &left[(0.5 * SAMPLE_RATE) as usize..][..4410];
+    assert!(first_press.iter().any(|sample| *sample != 0.0), "Noise note should be audible");
+    assert_ne!(first_press, second_press, "Pressing the key again shouldn't replay the same noise");
+
+    // Undetuned unison voices are mixed at 1 / voices each, so identical noise would add up to
+    // the level of a single voice while independent noise is 3dB quieter
+    let single = rms(&render_left(vec![SynthEvent::ChangeSoundGenOscParams(params[0].clone())], 60, 0.5, 0.2));
+    params[0].unisons = 2;
+    params[0].unison_detune_pct = 0.0;
+    params[0].unison_spread = 0.0;
+    let unison = rms(&render_left(vec![SynthEvent::ChangeSoundGenOscParams(params[0].clone())], 60, 0.5, 0.2));
+    assert!((unison / single - 0.5f32.sqrt()).abs() < 0.05, "Unison noise voices should be independent, {} against a single voice at {}", unison, single);
+}
@@ -0,0 +1,182 @@
+mod common;
+
+use std::sync::Arc;
+
+use oxidizer::constants::SAMPLE_RATE;
+use oxidizer::oscillator::Oscillator;
+use oxidizer::wavetables::WaveTable;
+use oxidizer::wavetype::{WaveType, OscillatorQuality};
+
+use common::power_at;
+
+// High enough that the naive wave forms alias badly, integer so one second of samples holds
+// a whole number of cycles and every component lands on a 1Hz bin
+const TEST_FREQUENCY: u32 = 2489;
+const HARMONICS_TO_CHECK: u32 = 200;
+
+fn render(wave_type: WaveType, quality: OscillatorQuality) -> Vec<f32> {
+    let mut osc = Oscillator::new(TEST_FREQUENCY as f32, wave_type, 0.0);
+    osc.set_quality(quality);
+
+    return (0..SAMPLE_RATE as usize).map(|_| osc.get_sample(0.0, 0.0)).collect();
+}
+
+// Ratio in dB of the power in harmonics folded back below Nyquist to the power in the
+// harmonics that really are below Nyquist
+fn aliasing_db(samples: &[f32]) -> f64 {
+    let sample_rate = SAMPLE_RATE as u32;
+    let nyquist = sample_rate / 2;
+
+    let mut wanted = 0.0;
+    let mut aliased = 0.0;
+    let mut seen: Vec<u32> = Vec::new();
+
+    for harmonic in 1..=HARMONICS_TO_CHECK {
+        let frequency = harmonic * TEST_FREQUENCY;
+        if frequency < nyquist {
+            wanted += power_at(samples, frequency as f32);
+            continue;
+        }
+
+        let folded = frequency % sample_rate;
+        let folded = if folded > nyquist { sample_rate - folded } else { folded };
+        if folded == 0 || folded % TEST_FREQUENCY == 0 || seen.contains(&folded) {
+            continue;
+        }
+
+        seen.push(folded);
+        aliased += power_at(samples, folded as f32);
+    }
+
+    return 10.0 * (aliased / wanted).log10();
+}
+
+#[test]
+fn band_limited_wave_forms_alias_less_than_naive(){
+    for wave_type in [WaveType::Saw, WaveType::Square, WaveType::Pulse, WaveType::Triangle] {
+        let naive = aliasing_db(&render(wave_type, OscillatorQuality::Naive));
+        let band_limited = aliasing_db(&render(wave_type, OscillatorQuality::BandLimited));
+
+        assert!(band_limited < naive - 8.0, "{:?} band limited aliasing {:.1}dB isn't well below naive {:.1}dB", wave_type, band_limited, naive);
+        assert!(band_limited < -20.0, "{:?} band limited aliasing {:.1}dB is too high, naive is {:.1}dB", wave_type, band_limited, naive);
+    }
+}
+
+#[test]
+fn band_limited_wave_forms_stay_in_range(){
+    for wave_type in [WaveType::Sin, WaveType::Saw, WaveType::Square, WaveType::Pulse, WaveType::Triangle] {
+        let samples = render(wave_type, OscillatorQuality::BandLimited);
+        let peak = samples.iter().fold(0.0f32, |peak, sample| peak.max(sample.abs()));
+
+        assert!(peak > 0.5 && peak <= 1.1, "{:?} peaks at {}", wave_type, peak);
+    }
+}
+
+#[test]
+fn wavetable_playback_follows_analytic_sine(){
+    let analytic = render(WaveType::Sin, OscillatorQuality::Naive);
+
+    for (quality, tolerance) in [(OscillatorQuality::WavetableLinear, 1e-3), (OscillatorQuality::WavetableCubic, 1e-4)] {
+        let wavetable = render(WaveType::Sin, quality);
+
+        let max_error = analytic.iter().zip(wavetable.iter()).fold(0.0f32, |max, (a, b)| max.max((a - b).abs()));
+        assert!(max_error < tolerance, "{:?} is {} away from the analytic sine", quality, max_error);
+    }
+}
+
+#[test]
+fn wavetable_position_morphs_between_frames(){
+    // Two frames of 4 samples, a flat 0.0 followed by a flat 1.0
+    let table = WaveTable::from_samples(vec![0.0, 0.0, 0.0, 0.0, 1.0, 1.0, 1.0, 1.0], 4).unwrap();
+    assert_eq!(table.get_frame_count(), 2);
+
+    assert_eq!(table.get_linear(0.3, 0.0), 0.0);
+    assert_eq!(table.get_linear(0.3, 1.0), 1.0);
+    assert!((table.get_cubic(0.6, 0.25) - 0.25).abs() < 1e-6, "Position should crossfade between frames");
+
+    let mut osc = Oscillator::new(440.0, WaveType::Sin, 0.0);
+    osc.set_wavetable(Some(Arc::new(table)));
+    osc.set_wavetable_position(0.5);
+    assert!((osc.get_sample(0.0, 0.0) - 0.5).abs() < 1e-6, "A loaded wavetable should replace the wave type");
+
+    let single_cycle = WaveTable::from_samples(vec![0.0, 1.0, 0.0], 2048).unwrap();
+    assert_eq!(single_cycle.get_frame_count(), 1, "Files shorter than a frame are a single cycle");
+    assert_eq!(single_cycle.wave_table_size, 3);
+}
+
+#[test]
+fn pulse_width_sets_the_duty_cycle(){
+    for quality in [OscillatorQuality::Naive, OscillatorQuality::BandLimited, OscillatorQuality::WavetableLinear, OscillatorQuality::WavetableCubic] {
+        for pulse_width in [0.1, 0.5, 0.75] {
+            let mut osc = Oscillator::new(100.0, WaveType::Pulse, 0.0);
+            osc.set_quality(quality);
+            osc.set_pulse_width(pulse_width);
+
+            // A pulse spending w of each cycle at 1.0 and the rest at -1.0 averages 2w - 1
+            let samples: Vec<f32> = (0..SAMPLE_RATE as usize).map(|_| osc.get_sample(0.0, 0.0)).collect();
+            let mean = samples.iter().sum::<f32>() / samples.len() as f32;
+
+            assert!((mean - (2.0 * pulse_width - 1.0)).abs() < 0.01, "{:?} pulse at width {} averages {}", quality, pulse_width, mean);
+        }
+    }
+}
+
+fn max_step(samples: &[f32]) -> f32 {
+    return samples.windows(2).fold(0.0f32, |max, pair| max.max((pair[1] - pair[0]).abs()));
+}
+
+#[test]
+fn phase_is_continuous_across_seconds_and_frequency_changes(){
+    // Not a whole number of Hz, so wrapping at one second would click
+    let frequency = 440.37;
+    let mut osc = Oscillator::new(frequency, WaveType::Sin, 0.0);
+    osc.set_quality(OscillatorQuality::Naive);
+
+    let mut samples: Vec<f32> = (0..SAMPLE_RATE as usize * 2).map(|_| osc.get_sample(0.0, 0.0)).collect();
+
+    osc.set_frequency(frequency * 1.5);
+    samples.extend((0..1000).map(|_| osc.get_sample(0.0, 0.0)));
+
+    // A sine can't move further than 2 pi f / sample rate between samples
+    let limit = 2.0 * std::f32::consts::PI * frequency * 1.5 / SAMPLE_RATE;
+    assert!(max_step(&samples) <= limit * 1.01, "Sine jumped by {} between samples", max_step(&samples));
+}
+
+#[test]
+fn start_phase_and_reset(){
+    let mut osc = Oscillator::new(100.0, WaveType::Saw, 0.25);
+    osc.set_quality(OscillatorQuality::Naive);
+    assert!((osc.get_sample(0.0, 0.0) + 0.5).abs() < 1e-6, "Saw should start a quarter of the way up its ramp");
+
+    for _ in 0..123 {
+        osc.get_sample(0.0, 0.0);
+    }
+
+    osc.set_start_phase(0.5);
+    osc.reset_phase();
+    assert_eq!(osc.get_phase(), 0.5);
+    assert!(osc.get_sample(0.0, 0.0).abs() < 1e-6, "Saw should be at 0.0 half way up its ramp");
+}
+
+#[test]
+fn hard_sync_follows_the_master_cycle(){
+    // 100Hz is exactly 441 samples a cycle
+    let mut master = Oscillator::new(100.0, WaveType::Sin, 0.0);
+    let mut slave = Oscillator::new(263.7, WaveType::Saw, 0.0);
+    slave.set_quality(OscillatorQuality::Naive);
+
+    let samples: Vec<f32> = (0..SAMPLE_RATE as usize / 10).map(|_| {
+        master.get_sample(0.0, 0.0);
+        let sample = slave.get_sample(0.0, 0.0);
+
+        if let Some(offset) = master.get_sync_offset() {
+            slave.sync(offset);
+        }
+
+        sample
+    }).collect();
+
+    for i in 441..samples.len() {
+        assert!((samples[i] - samples[i - 441]).abs() < 1e-3, "Synced saw should repeat with the master, sample {} is {} but was {} a cycle before", i, samples[i], samples[i - 441]);
+    }
+}
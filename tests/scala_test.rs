@@ -0,0 +1,109 @@
+use oxidizer::scala::{Scale, KeyboardMapping, ScalaErrorKind};
+use oxidizer::tuning::Tuning;
+
+fn assert_frequency(actual: f32, expected: f32) {
+    assert!((actual - expected).abs() < 0.01, "Expected {}Hz but got {}Hz", expected, actual);
+}
+
+const JUST_MAJOR: &str = "! just.scl
+!
+Just intonation major
+ 7
+!
+9/8
+5/4
+4/3
+3/2
+5/3
+15/8
+2
+";
+
+#[test]
+fn equal_temperament_needs_at_least_one_note(){
+    assert!(matches!(Scale::equal_temperament(0).unwrap_err().kind, ScalaErrorKind::EmptyScale));
+
+    let scale = Scale::equal_temperament(19).unwrap();
+    assert_eq!(scale.len(), 19);
+    assert!((scale.get_cents(-1) + 1200.0 / 19.0).abs() < 1e-9, "Degrees below the root should wrap into the period below");
+}
+
+#[test]
+fn equal_tempered_scale_matches_default_tuning(){
+    let scale = Scale::parse("12 tone\n12\n100.0\n200.\n300.0\n400.0\n500.0\n600.0\n700.0\n800.0\n900.0\n1000.0\n1100.0\n2/1\n").unwrap();
+    assert_eq!(scale.len(), 12);
+
+    let default_tuning = Tuning::default();
+    let mut scala_tuning = Tuning::default();
+    scala_tuning.set_scale(Some(scale));
+
+    for note in 0..128 {
+        let expected = default_tuning.get_frequency(note);
+        let actual = scala_tuning.get_frequency(note);
+        assert!((actual / expected - 1.0).abs() < 1e-4, "Note {} expected {}Hz but got {}Hz", note, expected, actual);
+    }
+}
+
+#[test]
+fn keyboard_mapping_lays_scale_out_on_white_keys(){
+    let scale = Scale::parse(JUST_MAJOR).unwrap();
+    assert_eq!(scale.description, "Just intonation major");
+
+    // C major on the white keys, black keys unmapped, C4 at 261.63Hz
+    let keyboard_mapping = KeyboardMapping::parse("! white keys
+12
+0
+127
+60
+60
+261.63
+7
+0
+x
+1
+x
+2
+3
+x
+4
+x
+5
+x
+6
+").unwrap();
+
+    let mut tuning = Tuning::default();
+    tuning.set_scale(Some(scale));
+    tuning.set_keyboard_mapping(Some(keyboard_mapping));
+
+    assert_frequency(tuning.get_frequency(60), 261.63);
+    assert_frequency(tuning.get_frequency(64), 261.63 * 5.0 / 4.0);
+    assert_frequency(tuning.get_frequency(67), 261.63 * 3.0 / 2.0);
+    assert_frequency(tuning.get_frequency(71), 261.63 * 15.0 / 8.0);
+    assert_frequency(tuning.get_frequency(72), 261.63 * 2.0);
+    assert_frequency(tuning.get_frequency(57), 261.63 / 2.0 * 5.0 / 3.0);
+
+    assert!(!tuning.is_mapped(61), "Black keys should be unmapped");
+    assert!(tuning.is_mapped(62), "White keys should be mapped");
+}
+
+#[test]
+fn parse_errors_report_the_line(){
+    let error = Scale::parse("bad\n3\n100.0\nnine/eight\n1200.0\n").unwrap_err();
+    assert_eq!(error.line, 4);
+    assert!(matches!(error.kind, ScalaErrorKind::InvalidPitch(_)), "Expected an invalid pitch but got {}", error);
+
+    let error = Scale::parse("short\n3\n100.0\n1200.0\n").unwrap_err();
+    assert!(matches!(error.kind, ScalaErrorKind::WrongNoteCount { expected: 3, found: 2 }), "Expected a wrong note count but got {}", error);
+
+    // A huge note count shouldn't be trusted before the notes are read
+    let error = Scale::parse("huge\n18446744073709551615\n100.0\n").unwrap_err();
+    assert!(matches!(error.kind, ScalaErrorKind::WrongNoteCount { found: 1, .. }), "Expected a wrong note count but got {}", error);
+
+    let error = KeyboardMapping::parse("18446744073709551615\n0\n127\n60\n69\n440.0\n12\n0\n").unwrap_err();
+    assert!(matches!(error.kind, ScalaErrorKind::MapTooLarge(_)), "Expected a map that's too large but got {}", error);
+
+    let error = KeyboardMapping::parse("12\n0\n127\n60\n69\nhigh\n").unwrap_err();
+    assert_eq!(error.line, 6);
+    assert!(error.to_string().starts_with("line 6: expected the reference frequency"), "Unexpected message {}", error);
+}
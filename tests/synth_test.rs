@@ -1,6 +1,16 @@
+mod common;
+
 use std::sync::mpsc::{Sender, Receiver, channel};
 
-use oxidizer::synthesizer::{Synthesizer, SynthEvent};
+use oxidizer::constants::{SAMPLE_RATE, NUM_CHANNELS, OscNumber, OscillatorParam, SoundGenOscParams, SubOscParams, FmParams, FilterParam, ModEnvelopeNumber, ModEnvelopeParams, EnvelopeShape};
+use oxidizer::synthesizer::{Synthesizer, SynthEvent, TimedSynthEvent, EnvelopeParam};
+use oxidizer::envelope::{EnvelopeADSR, DEFAULT_SUSTAIN_AMPLITUDE};
+use oxidizer::preset::Preset;
+use oxidizer::sound_generator::SoundGenerator;
+use oxidizer::tuning::Tuning;
+use oxidizer::wavetype::{WaveType, OscillatorQuality, PhaseMode, OscModulation, FmAlgorithm, FilterMode, EnvelopeDestination, EnvelopeCurve, EnvelopeStage};
+
+use common::{power_at, render_left, render_events_left};
 
 #[test]
 fn do_thing(){
@@ -8,7 +18,572 @@ fn do_thing(){
 
     let mut synth = Synthesizer::new(synth_receiver);
 
-    let sample = synth.get_synth_sample();
+    let frame = synth.get_synth_frame();
+
+    assert_eq!(frame, [0.0, 0.0], "Initialized synth should return an empty frame");
+}
+
+#[test]
+fn note_envelope_is_reproducible(){
+    let render = || {
+        let (synth_sender, synth_receiver): (Sender<SynthEvent>, Receiver<SynthEvent>) = channel();
+        let mut synth = Synthesizer::new(synth_receiver);
+
+        let _ = synth_sender.send(SynthEvent::NotePress(57, 1.0));
+        let samples: Vec<[f32; 2]> = (0..4410).map(|_| synth.get_synth_frame()).collect();
+        std::thread::sleep(std::time::Duration::from_millis(5));
+
+        let _ = synth_sender.send(SynthEvent::NoteRelease(57));
+        let tail: Vec<[f32; 2]> = (0..4410).map(|_| synth.get_synth_frame()).collect();
+
+        assert_eq!(synth.get_sample_count(), 8820, "Clock should advance once per rendered sample");
+        return [samples, tail].concat();
+    };
+
+    assert_eq!(render(), render(), "Rendering the same events should produce identical samples");
+}
+
+
+#[test]
+fn notes_late_in_a_long_session_keep_sample_accurate_envelopes(){
+    // A short attack and release, pressed at the start and again after three hours of samples
+    let render = |press_sample: u64| {
+        let mut envelope = EnvelopeADSR::new();
+        envelope.set_attack_time(0.002);
+        envelope.set_release_time(0.003);
+
+        let tuning = Tuning::default();
+        let mut sound_generator = SoundGenerator::new();
+        sound_generator.note_pressed(57, 1.0, &tuning, press_sample);
+
+        let mut frames: Vec<[f32; 2]> = Vec::new();
+        for i in 0..2000 {
+            if i == 1000 {
+                sound_generator.note_released(57, press_sample + i);
+            }
+            frames.push(sound_generator.get_frame(&envelope, 0.0, 0.0, 0.0, press_sample + i));
+        }
+
+        frames
+    };
+
+    let three_hours = (3.0 * 60.0 * 60.0 * SAMPLE_RATE) as u64;
+    assert_eq!(render(three_hours), render(0), "A note should sound the same whenever it's pressed");
+}
+
+#[test]
+fn offline_render_applies_events_at_their_time(){
+    let mut synth = Synthesizer::new_offline();
+
+    let events = vec![
+        TimedSynthEvent::new(0.5, SynthEvent::NoteRelease(57)),
+        TimedSynthEvent::new(0.1, SynthEvent::NotePress(57, 1.0)),
+    ];
+
+    let buffer = synth.render(events, 1.0);
+    let at = |time: f32| (time * SAMPLE_RATE) as usize * NUM_CHANNELS as usize;
+
+    assert_eq!(buffer.len(), at(1.0), "Render should produce exactly the requested duration");
+    assert!(buffer[..at(0.1)].iter().all(|s| *s == 0.0), "Nothing should sound before the note is pressed");
+    assert!(buffer[at(0.1)..at(0.5)].iter().any(|s| *s != 0.0), "The note should sound while held");
+    assert!(buffer[at(0.7)..].iter().all(|s| *s == 0.0), "The note should be silent once its release has finished");
+}
+
+
+#[test]
+fn sustain_and_envelope_shape_events_reach_the_envelope(){
+    let peak_level = |mut events: Vec<TimedSynthEvent>| {
+        events.push(TimedSynthEvent::new(0.0, SynthEvent::NotePress(57, 1.0)));
+
+        // Held well past the decay, then the release is measured halfway through
+        events.push(TimedSynthEvent::new(1.5, SynthEvent::NoteRelease(57)));
+        events.push(TimedSynthEvent::new(0.0, SynthEvent::ChangeEnvelope(EnvelopeParam::ReleaseTime, 0.2)));
+
+        let buffer = Synthesizer::new_offline().render(events, 1.7);
+        let level = |from: f32, to: f32| buffer[(from * SAMPLE_RATE) as usize * 2..(to * SAMPLE_RATE) as usize * 2].iter().fold(0.0, |peak: f32, s| peak.max(s.abs()));
+        (level(1.2, 1.5), level(1.59, 1.61))
+    };
+
+    let (sustain, release) = peak_level(vec![]);
+    let (quiet_sustain, _) = peak_level(vec![TimedSynthEvent::new(0.0, SynthEvent::ChangeEnvelope(EnvelopeParam::SustainAmplitude, DEFAULT_SUSTAIN_AMPLITUDE / 2.0))]);
+    assert!((quiet_sustain / sustain - 0.5).abs() < 0.01, "Halving the sustain amplitude should halve the held level, {} against {}", quiet_sustain, sustain);
+
+    let exponential = EnvelopeShape { curve: EnvelopeCurve::Exponential, tension: 0.5 };
+    let (_, exponential_release) = peak_level(vec![TimedSynthEvent::new(0.0, SynthEvent::ChangeEnvelopeShape(EnvelopeStage::Release, exponential))]);
+    assert!(exponential_release < release * 0.8, "An exponential release should have fallen further halfway through, {} against {}", exponential_release, release);
+}
+
+#[test]
+fn velocity_scales_note_level(){
+    let peak = |velocity: f32| {
+        let mut synth = Synthesizer::new_offline();
+        let events = vec![TimedSynthEvent::new(0.0, SynthEvent::NotePress(57, velocity))];
+
+        synth.render(events, 0.5).iter().fold(0.0, |peak: f32, s| peak.max(s.abs()))
+    };
+
+    let full = peak(1.0);
+    let half = peak(0.5);
+
+    assert!(full > 0.0);
+    assert!((half / full - 0.5).abs() < 0.01, "Half velocity should be half as loud with the default linear curve");
+}
+
+
+#[test]
+fn oscillator_level_and_pitch_offsets(){
+    let render = |param: Option<(OscillatorParam, f32)>| {
+        let mut synth = Synthesizer::new_offline();
+        let mut events = vec![TimedSynthEvent::new(0.0, SynthEvent::NotePress(57, 1.0))];
+        if let Some((param, value)) = param {
+            events.insert(0, TimedSynthEvent::new(0.0, SynthEvent::ChangeOscillator(OscNumber::Osc1, param, value)));
+        }
+
+        // Left channel only
+        synth.render(events, 0.5).into_iter().step_by(NUM_CHANNELS as usize).collect::<Vec<f32>>()
+    };
+    let peak = |samples: &[f32]| samples.iter().fold(0.0, |peak: f32, s| peak.max(s.abs()));
+    let rising_crossings = |samples: &[f32]| samples.windows(2).filter(|pair| pair[0] < 0.0 && pair[1] >= 0.0).count() as f32;
+
+    let plain = render(None);
+    let quieter = render(Some((OscillatorParam::Gain, -6.0)));
+    assert!((peak(&quieter) / peak(&plain) - 0.501).abs() < 0.01, "-6dB should halve the oscillator's level");
+
+    // A3 is 220Hz, so half a second holds 110 cycles
+    let crossings = rising_crossings(&plain);
+    assert!((crossings - 110.0).abs() <= 1.0, "A3 crossed zero {} times", crossings);
+
+    for (param, value, ratio) in [(OscillatorParam::Octave, 1.0, 2.0), (OscillatorParam::Semitone, -12.0, 0.5), (OscillatorParam::FineTune, 100.0, (1.0f32 / 12.0).exp2())] {
+        let crossings = rising_crossings(&render(Some((param, value))));
+        assert!((crossings - 110.0 * ratio).abs() <= 1.0, "Expected {} rising zero crossings but found {}", 110.0 * ratio, crossings);
+    }
+}
+
+
+fn left_and_right_levels(synth_events: Vec<SynthEvent>) -> (f32, f32) {
+    let mut synth = Synthesizer::new_offline();
+    let mut events: Vec<TimedSynthEvent> = synth_events.into_iter().map(|event| TimedSynthEvent::new(0.0, event)).collect();
+    events.push(TimedSynthEvent::new(0.0, SynthEvent::NotePress(57, 1.0)));
+
+    let buffer = synth.render(events, 0.5);
+    let rms = |channel: usize| {
+        let power: f32 = buffer.iter().skip(channel).step_by(NUM_CHANNELS as usize).map(|s| s * s).sum();
+        (power / (buffer.len() / NUM_CHANNELS as usize) as f32).sqrt()
+    };
+
+    return (rms(0), rms(1));
+}
+
+#[test]
+fn oscillators_pan_across_the_stereo_field(){
+    let (left, right) = left_and_right_levels(vec![]);
+    assert!(left > 0.0 && (left - right).abs() < 1e-6, "A centred oscillator should be equally loud on both sides");
+
+    let (left, right) = left_and_right_levels(vec![SynthEvent::ChangeOscillator(OscNumber::Osc1, OscillatorParam::Pan, -1.0)]);
+    assert!(left > 0.0 && right < 1e-6, "Hard left should be silent on the right");
+
+    let (left, right) = left_and_right_levels(vec![SynthEvent::ChangeOscillator(OscNumber::Osc1, OscillatorParam::Pan, 0.5)]);
+    assert!(right > left, "Panning right should favour the right channel");
+
+    // Equal power, the total stays the same wherever the oscillator is panned
+    let (centre_left, centre_right) = left_and_right_levels(vec![]);
+    assert!(((left * left + right * right) - (centre_left * centre_left + centre_right * centre_right)).abs() < 1e-3);
+}
+
+#[test]
+fn unison_spread_widens_the_stereo_image(){
+    let side_level = |spread: f32| {
+        let mut synth = Synthesizer::new_offline();
+        let mut params = SoundGenOscParams::create_default_array();
+        params[0].unisons = 2;
+        params[0].unison_spread = spread;
+
+        let events = vec![
+            TimedSynthEvent::new(0.0, SynthEvent::ChangeSoundGenOscParams(params[0].clone())),
+            TimedSynthEvent::new(0.0, SynthEvent::NotePress(57, 1.0)),
+        ];
+
+        // Difference between the channels is zero for a mono image
+        synth.render(events, 0.5).chunks(NUM_CHANNELS as usize).fold(0.0, |max: f32, frame| max.max((frame[0] - frame[1]).abs()))
+    };
+
+    assert!(side_level(0.0) < 1e-6, "Unspread voices should sit in the centre");
+    assert!(side_level(1.0) > side_level(0.5), "Wider spread should push the voices further apart");
+}
+
+
+fn render_unison_sine(phase_mode: PhaseMode, start_phase: f32, press_sample: u64) -> Vec<f32> {
+    let mut params = SoundGenOscParams::create_default_array();
+    params[0].quality = OscillatorQuality::Naive;
+    params[0].unisons = 4;
+    params[0].unison_detune_pct = 0.0;
+    params[0].unison_spread = 0.0;
+    params[0].phase_mode = phase_mode;
+    params[0].start_phase = start_phase;
+
+    let events = vec![
+        TimedSynthEvent::at_sample(0, SynthEvent::ChangeSoundGenOscParams(params[0].clone())),
+        TimedSynthEvent::at_sample(press_sample, SynthEvent::NotePress(69, 1.0)),
+    ];
+
+    return Synthesizer::new_offline().render(events, 0.5);
+}
+
+#[test]
+fn unison_phase_modes(){
+    let peak = |samples: &[f32]| samples.iter().fold(0.0, |peak: f32, s| peak.max(s.abs()));
+
+    // Without detune, voices starting together add up to the full level and random phases
+    // partly cancel
+    let fixed = render_unison_sine(PhaseMode::Fixed, 0.0, 0);
+    let random = render_unison_sine(PhaseMode::Random, 0.0, 0);
+    assert!(peak(&random) < peak(&fixed) * 0.99, "Random phases should stop the voices lining up");
+    assert_ne!(random, render_unison_sine(PhaseMode::Random, 0.0, 100), "Each note should get new random phases");
+
+    // Free running voices pick up where an oscillator started with the synth would be
+    let press_sample = 11111;
+    let phase = (Tuning::default().get_frequency(69) as f64 * press_sample as f64 / SAMPLE_RATE as f64).fract() as f32;
+
+    let free_running = render_unison_sine(PhaseMode::FreeRunning, 0.0, press_sample);
+    let fixed = render_unison_sine(PhaseMode::Fixed, phase, press_sample);
+    let max_error = free_running.iter().zip(fixed.iter()).fold(0.0f32, |max, (a, b)| max.max((a - b).abs()));
+    assert!(max_error < 1e-4, "Free running voices are {} away from the expected phase", max_error);
+}
+
+#[test]
+fn free_running_phase_holds_in_long_sessions(){
+    let render = |phase_mode: PhaseMode, start_phase: f32, press_sample: u64| {
+        let mut params = SoundGenOscParams::create_default_array();
+        params[0].quality = OscillatorQuality::Naive;
+        params[0].phase_mode = phase_mode;
+        params[0].start_phase = start_phase;
+
+        let tuning = Tuning::default();
+        let mut sound_generator = SoundGenerator::new();
+        sound_generator.update_oscillator_params(params[0].clone(), &tuning, press_sample);
+        sound_generator.note_pressed(69, 1.0, &tuning, press_sample);
+
+        (0..1000).map(|i| sound_generator.get_frame(&EnvelopeADSR::new(), 0.0, 0.0, 0.0, press_sample + i)[0]).collect::<Vec<f32>>()
+    };
+
+    // An hour and a bit in, where seconds in f32 are already several samples apart
+    let press_sample = (60.0 * 60.0 * SAMPLE_RATE) as u64 + 11111;
+    let phase = (440.0 * press_sample as f64 / SAMPLE_RATE as f64).fract() as f32;
+
+    let free_running = render(PhaseMode::FreeRunning, 0.0, press_sample);
+    let fixed = render(PhaseMode::Fixed, phase, press_sample);
+    let max_error = free_running.iter().zip(fixed.iter()).fold(0.0f32, |max, (a, b)| max.max((a - b).abs()));
+    assert!(max_error < 1e-4, "Free running voices are {} away from the expected phase after an hour", max_error);
+}
+
+#[test]
+fn ring_and_amplitude_modulation(){
+    // A3 at 220Hz modulating a sine two octaves up at 880Hz, with the modulator itself muted
+    let spectrum = |modulation: OscModulation| {
+        let mut params = SoundGenOscParams::create_default_array();
+        params[0].gain = f32::NEG_INFINITY;
+        params[1].enabled = true;
+        params[1].octave = 2;
+        params[1].modulation = modulation;
+        params[1].modulator = OscNumber::Osc1;
+
+        // The held part of the note, half a second fits every frequency checked
+        let setup = vec![
+            SynthEvent::ChangeSoundGenOscParams(params[0].clone()),
+            SynthEvent::ChangeSoundGenOscParams(params[1].clone()),
+        ];
+        let held = render_left(setup, 57, 0.75, 0.25);
+
+        [660.0, 880.0, 1100.0].map(|frequency| power_at(&held, frequency))
+    };
+
+    let [lower, carrier, upper] = spectrum(OscModulation::Off);
+    assert!(lower < carrier * 1e-4 && upper < carrier * 1e-4, "Unmodulated sine should have no sidebands");
+
+    let [lower, carrier, upper] = spectrum(OscModulation::RingMod);
+    assert!(carrier < lower * 1e-3, "Ring modulation should remove the carrier");
+    assert!((lower / upper - 1.0).abs() < 0.05, "Ring modulation sidebands should be equal");
+
+    let [lower, carrier, upper] = spectrum(OscModulation::AmplitudeMod);
+    assert!(lower > carrier * 0.01 && upper > carrier * 0.01, "Amplitude modulation should add sidebands");
+    assert!(carrier > lower, "Amplitude modulation should keep the carrier");
+}
+
+#[test]
+fn fm_algorithms_modulate_carriers_with_an_enveloped_index(){
+    // A3 at 220Hz modulated by a sine an octave up, so the sidebands land on 660Hz and 1100Hz
+    let render = |fm_params: FmParams, held_from: f32| {
+        let mut params = SoundGenOscParams::create_default_array();
+        params[1].enabled = true;
+        params[1].octave = 1;
+
+        let setup = vec![
+            SynthEvent::ChangeSoundGenOscParams(params[1].clone()),
+            SynthEvent::ChangeFmParams(fm_params),
+        ];
+        let held = render_left(setup, 57, held_from + 0.5, held_from);
+
+        [220.0, 440.0, 660.0].map(|frequency| power_at(&held, frequency))
+    };
+
+    let sustained = FmParams { algorithm: FmAlgorithm::Pair, index: 2.0, attack: 0.0, decay: 0.0, sustain: 1.0, release: 0.1 };
+
+    let [carrier, modulator, sideband] = render(FmParams::default(), 0.25);
+    assert!(sideband < carrier * 1e-4, "Without FM there should be no sidebands");
+    assert!(modulator > carrier * 0.5, "Without FM both oscillators should be heard");
+
+    let [carrier, modulator, sideband] = render(sustained.clone(), 0.25);
+    assert!(sideband > carrier * 0.05, "FM should add sidebands around the carrier");
+    assert!(modulator < sideband * 1e-3, "The modulator shouldn't be heard on its own");
+
+    // An index that decays to nothing leaves the pure carrier
+    let [carrier, _, sideband] = render(FmParams { decay: 0.2, sustain: 0.0, ..sustained }, 0.5);
+    assert!(sideband < carrier * 1e-4, "FM index should follow its envelope down to zero");
+}
+
+#[test]
+fn instant_fm_index_release_stays_finite(){
+    let mut params = SoundGenOscParams::create_default_array();
+    params[1].enabled = true;
+    params[1].octave = 1;
+    let fm_params = FmParams { algorithm: FmAlgorithm::Pair, index: 2.0, attack: 0.0, decay: 0.0, sustain: 1.0, release: 0.0 };
+
+    let events = vec![
+        TimedSynthEvent::new(0.0, SynthEvent::ChangeSoundGenOscParams(params[1].clone())),
+        TimedSynthEvent::new(0.0, SynthEvent::ChangeFmParams(fm_params)),
+        TimedSynthEvent::new(0.0, SynthEvent::NotePress(57, 1.0)),
+        TimedSynthEvent::new(0.2, SynthEvent::NoteRelease(57)),
+    ];
+
+    let left = render_events_left(events, 0.4, 0.0);
+    assert!(left.iter().all(|sample| sample.is_finite()), "Releasing a note with an instant FM index release should stay finite");
+    assert!(left[(0.25 * SAMPLE_RATE) as usize..].iter().any(|sample| *sample != 0.0), "The note should still be in its amplitude release");
+}
+
+#[test]
+fn sub_oscillator_plays_below_the_note(){
+    let render = |sub_osc: SubOscParams| {
+        let mut params = SoundGenOscParams::create_default_array();
+        params[0].enabled = false;
+
+        render_left(vec![SynthEvent::ChangeSoundGenOscParams(params[0].clone()), SynthEvent::ChangeSubOscParams(sub_osc)], 57, 1.0, 0.0)
+    };
+    let rising_crossings = |samples: &[f32]| samples.windows(2).filter(|pair| pair[0] < 0.0 && pair[1] >= 0.0).count() as f32;
+
+    assert!(render(SubOscParams::default()).iter().all(|s| *s == 0.0), "Disabled sub oscillator should be silent");
+
+    // A3 is 220Hz, so one second holds 110 cycles an octave down and 55 two octaves down
+    for (octave, cycles) in [(-1, 110.0), (-2, 55.0)] {
+        let samples = render(SubOscParams { enabled: true, wave_type: WaveType::Sin, octave, gain: 0.0 });
+        let crossings = rising_crossings(&samples);
+        assert!((crossings - cycles).abs() <= 1.0, "Sub oscillator {} octaves down crossed zero {} times", octave, crossings);
+    }
+}
+
+#[test]
+fn filter_cutoff_follows_key_velocity_and_envelope(){
+    // Power of a saw's 8th harmonic, well above a 200Hz low pass cutoff. Every note is at full
+    // velocity so only the filter changes the level.
+    let brightness = |note: i32, filter_events: Vec<SynthEvent>| {
+        let mut params = SoundGenOscParams::create_default_array();
+        params[0].wave_type = WaveType::Saw;
+
+        let mut setup = vec![
+            SynthEvent::ChangeSoundGenOscParams(params[0].clone()),
+            SynthEvent::ChangeFilterMode(FilterMode::LowPass),
+            SynthEvent::ChangeFilter(FilterParam::Cutoff, 200.0),
+        ];
+        setup.extend(filter_events);
+
+        // Once the envelopes have settled
+        let samples = render_left(setup, note, 2.0, 1.0);
+        power_at(&samples, Tuning::default().get_frequency(note) * 8.0)
+    };
+
+    let base = brightness(57, vec![]);
+    let unfiltered = brightness(57, vec![SynthEvent::ChangeFilterMode(FilterMode::Off)]);
+    let velocity = brightness(57, vec![SynthEvent::ChangeFilter(FilterParam::VelocityAmount, 3.0)]);
+    let mut filter_envelope = ModEnvelopeParams::create_default_array()[ModEnvelopeNumber::Filter as usize].clone();
+    filter_envelope.amount = 3.0;
+    filter_envelope.sustain = 1.0;
+    let envelope = brightness(57, vec![SynthEvent::ChangeModEnvelopeParams(filter_envelope)]);
+
+    assert!(base < unfiltered / 100.0, "Low pass should cut the 8th harmonic, {} against {} unfiltered", base, unfiltered);
+    assert!(velocity > base * 10.0, "Velocity should open the filter, {} against {}", velocity, base);
+    assert!(envelope > base * 10.0, "The filter envelope should open the filter, {} against {}", envelope, base);
+
+    // Full key tracking keeps the cutoff the same distance from the note, two octaves up here
+    let untracked = brightness(81, vec![]);
+    let tracked = brightness(81, vec![SynthEvent::ChangeFilter(FilterParam::KeyTracking, 1.0)]);
+    let tracked_base = brightness(57, vec![SynthEvent::ChangeFilter(FilterParam::KeyTracking, 1.0)]);
+    assert!(untracked < base / 100.0, "Without key tracking higher notes should sound darker, {} against {}", untracked, base);
+    assert!((tracked / tracked_base).log2().abs() < 1.0, "With full key tracking both notes should sound as bright, {} against {}", tracked, tracked_base);
+}
+
+#[test]
+fn mod_envelopes_reach_each_destination(){
+    // A3 with a sustained mod envelope sent to the destination, returns the power at each frequency
+    // over the last half second of the note
+    let render = |params: SoundGenOscParams, fm_params: FmParams, destination: EnvelopeDestination, amount: f32, frequencies: [f32; 2]| {
+        let mut mod_envelope = ModEnvelopeParams::create_default_array()[ModEnvelopeNumber::Mod as usize].clone();
+        mod_envelope.destination = destination;
+        mod_envelope.amount = amount;
+        mod_envelope.sustain = 1.0;
+        mod_envelope.decay = 0.1;
+
+        let mut osc2 = SoundGenOscParams::create_default_array()[1].clone();
+        osc2.enabled = fm_params.algorithm != FmAlgorithm::Off;
+        osc2.octave = 1;
+
+        let setup = vec![
+            SynthEvent::ChangeSoundGenOscParams(params),
+            SynthEvent::ChangeSoundGenOscParams(osc2),
+            SynthEvent::ChangeFmParams(fm_params),
+            SynthEvent::ChangeModEnvelopeParams(mod_envelope),
+        ];
+
+        let left = render_left(setup, 57, 1.0, 0.5);
+        frequencies.map(|frequency| power_at(&left, frequency))
+    };
+    let sine = SoundGenOscParams::create_default_array()[0].clone();
+
+    // Twelve semitones of pitch moves the note up an octave
+    let [note, octave_up] = render(sine.clone(), FmParams::default(), EnvelopeDestination::Pitch, 12.0, [220.0, 440.0]);
+    assert!(octave_up > note * 1000.0, "Pitch envelope should move the note up an octave, {} against {}", octave_up, note);
+
+    // A square has no even harmonics until the envelope moves its width off 50%
+    let mut pulse = sine.clone();
+    pulse.wave_type = WaveType::Pulse;
+    pulse.pulse_width = 0.5;
+    let [fundamental, second] = render(pulse.clone(), FmParams::default(), EnvelopeDestination::PulseWidth, 0.0, [220.0, 440.0]);
+    assert!(second < fundamental * 1e-3, "A square shouldn't have a second harmonic");
+    let [fundamental, second] = render(pulse, FmParams::default(), EnvelopeDestination::PulseWidth, 0.25, [220.0, 440.0]);
+    assert!(second > fundamental * 0.1, "Pulse width envelope should narrow the pulse, {} against {}", second, fundamental);
+
+    // FM with no index of its own gets all of it from the envelope
+    let no_index = FmParams { algorithm: FmAlgorithm::Pair, index: 0.0, attack: 0.0, decay: 0.0, sustain: 1.0, release: 0.1 };
+    let [carrier, sideband] = render(sine.clone(), no_index.clone(), EnvelopeDestination::FmIndex, 0.0, [220.0, 660.0]);
+    assert!(sideband < carrier * 1e-4, "Without an index there should be no sidebands");
+    let [carrier, sideband] = render(sine, no_index, EnvelopeDestination::FmIndex, 2.0, [220.0, 660.0]);
+    assert!(sideband > carrier * 0.05, "FM index envelope should add sidebands, {} against {}", sideband, carrier);
+}
+
+#[test]
+fn mod_envelopes_run_separately_on_each_note(){
+    // Pitch drops an octave over the decay, so a note pressed later is still higher than one pressed earlier
+    let mut mod_envelope = ModEnvelopeParams::create_default_array()[ModEnvelopeNumber::Mod as usize].clone();
+    mod_envelope.amount = 12.0;
+    mod_envelope.decay = 0.5;
+
+    let events = vec![
+        TimedSynthEvent::new(0.0, SynthEvent::ChangeModEnvelopeParams(mod_envelope)),
+        TimedSynthEvent::new(0.0, SynthEvent::NotePress(57, 1.0)),
+        TimedSynthEvent::new(0.4, SynthEvent::NotePress(45, 1.0)),
+    ];
+
+    let left = render_events_left(events, 0.6, 0.5);
+
+    // By 0.5s the first note has settled back to 220Hz while the second, an octave lower, is
+    // only 0.1s into its decay and still most of an octave above its 110Hz
+    let first = power_at(&left, 220.0);
+    let second_settled = power_at(&left, 110.0);
+    assert!(first > second_settled * 10.0, "The second note's envelope should still be high while the first has finished, {} against {}", first, second_settled);
+}
+
+#[test]
+fn instant_mod_envelope_releases_stay_finite(){
+    // Both envelopes with no release, the filter one on a low pass and the mod one on pitch
+    let mut setup = vec![SynthEvent::ChangeFilterMode(FilterMode::LowPass)];
+    for mut mod_envelope in ModEnvelopeParams::create_default_array() {
+        mod_envelope.release = 0.0;
+        setup.push(SynthEvent::ChangeModEnvelopeParams(mod_envelope));
+    }
+
+    let mut events: Vec<TimedSynthEvent> = setup.into_iter().map(|event| TimedSynthEvent::new(0.0, event)).collect();
+    events.push(TimedSynthEvent::new(0.0, SynthEvent::NotePress(57, 1.0)));
+    events.push(TimedSynthEvent::new(0.2, SynthEvent::NoteRelease(57)));
+
+    let left = render_events_left(events, 0.4, 0.0);
+    assert!(left.iter().all(|sample| sample.is_finite()), "Releasing a note with instant mod envelope releases should stay finite");
+    assert!(left[(0.25 * SAMPLE_RATE) as usize..].iter().any(|sample| *sample != 0.0), "The note should still be in its amplitude release");
+}
+
+fn peak(samples: &[f32]) -> f32 {
+    return samples.iter().fold(0.0, |peak, sample| peak.max(sample.abs()));
+}
+
+fn seconds(time: f32) -> usize {
+    return (time * SAMPLE_RATE) as usize;
+}
+
+#[test]
+fn sustain_pedal_holds_released_notes(){
+    let events = vec![
+        TimedSynthEvent::new(0.0, SynthEvent::ControlChange(64, 127)),
+        TimedSynthEvent::new(0.0, SynthEvent::NotePress(57, 1.0)),
+        TimedSynthEvent::new(0.2, SynthEvent::NoteRelease(57)),
+        TimedSynthEvent::new(0.5, SynthEvent::ControlChange(64, 0)),
+    ];
+    let left = render_events_left(events, 0.8, 0.0);
+
+    assert!(peak(&left[seconds(0.4)..seconds(0.5)]) > 0.05, "The pedal should hold a released note");
+    assert_eq!(peak(&left[seconds(0.65)..]), 0.0, "Lifting the pedal should release the note");
+}
+
+#[test]
+fn pitch_bend_retunes_held_notes(){
+    let events = vec![
+        TimedSynthEvent::new(0.0, SynthEvent::NotePress(57, 1.0)),
+        TimedSynthEvent::new(0.1, SynthEvent::PitchBend(1.0)),
+    ];
+    let left = render_events_left(events, 1.2, 0.2);
+
+    // A full bend up is two semitones
+    let bent = power_at(&left, 220.0 * (2.0 as f32).powf(2.0 / 12.0));
+    let unbent = power_at(&left, 220.0);
+    assert!(bent > unbent * 100.0, "A held note should follow the bend, {} at the bent pitch against {}", bent, unbent);
+}
+
+#[test]
+fn mod_wheel_and_aftertouch_add_vibrato(){
+    let steady = power_at(&render_left(vec![], 57, 1.2, 0.2), 220.0);
+
+    for modulation in [SynthEvent::ControlChange(1, 127), SynthEvent::ChannelPressure(1.0)] {
+        let vibrato = power_at(&render_left(vec![modulation], 57, 1.2, 0.2), 220.0);
+        assert!(vibrato < steady * 0.5, "Vibrato should spread the note away from its pitch, {} against {} without", vibrato, steady);
+    }
+}
+
+#[test]
+fn all_notes_off_releases_and_all_sound_off_silences(){
+    let events = vec![
+        TimedSynthEvent::new(0.0, SynthEvent::NotePress(57, 1.0)),
+        TimedSynthEvent::new(0.3, SynthEvent::ControlChange(123, 0)),
+    ];
+    let left = render_events_left(events, 0.6, 0.0);
+    assert!(peak(&left[seconds(0.3) + 1..seconds(0.32)]) > 0.05, "All notes off should let notes release");
+    assert_eq!(peak(&left[seconds(0.45)..]), 0.0, "All notes off should release every note");
+
+    // Even notes held by the pedal stop at once
+    let events = vec![
+        TimedSynthEvent::new(0.0, SynthEvent::ControlChange(64, 127)),
+        TimedSynthEvent::new(0.0, SynthEvent::NotePress(57, 1.0)),
+        TimedSynthEvent::new(0.0, SynthEvent::NotePress(64, 1.0)),
+        TimedSynthEvent::new(0.2, SynthEvent::NoteRelease(64)),
+        TimedSynthEvent::new(0.3, SynthEvent::ControlChange(120, 0)),
+    ];
+    let left = render_events_left(events, 0.6, 0.0);
+    assert!(peak(&left[seconds(0.25)..seconds(0.3)]) > 0.05, "Notes should sound before all sound off");
+    assert_eq!(peak(&left[seconds(0.3) + 1..]), 0.0, "All sound off should silence every note immediately");
+}
+
+#[test]
+fn program_change_applies_a_preset(){
+    let default = render_left(vec![], 57, 0.3, 0.0);
 
-    assert_eq!(sample, 0.0, "Initialized synth should return an empty sample");
-}
\ No newline at end of file
+    for (program, preset) in Preset::get_presets().iter().enumerate() {
+        let changed = render_left(vec![SynthEvent::ProgramChange(program as u8)], 57, 0.3, 0.0);
+        assert_eq!(changed, render_left(preset.to_synth_events(), 57, 0.3, 0.0), "Program {} should apply the {} preset", program, preset.name);
+        assert!(program == 0 || changed != default, "Program {} should change the sound", program);
+    }
+}
@@ -0,0 +1,47 @@
+mod common;
+
+use oxidizer::synthesizer::{SynthEvent, TimedSynthEvent, TuningParam};
+use oxidizer::tuning::Tuning;
+
+use common::{power_at, render_events_left};
+
+fn assert_frequency(actual: f32, expected: f32) {
+    assert!((actual - expected).abs() < 0.01, "Expected {}Hz but got {}Hz", expected, actual);
+}
+
+#[test]
+fn midi_notes_map_to_equal_temperament(){
+    let tuning = Tuning::default();
+
+    assert_frequency(tuning.get_frequency(69), 440.0);
+    assert_frequency(tuning.get_frequency(57), 220.0);
+    assert_frequency(tuning.get_frequency(60), 261.63);
+    assert_frequency(tuning.get_frequency(81), 880.0);
+}
+
+#[test]
+fn reference_transpose_and_fine_tune_shift_every_note(){
+    let mut tuning = Tuning::new(432.0);
+    assert_frequency(tuning.get_frequency(69), 432.0);
+
+    tuning.set_a4_frequency(415.0);
+    tuning.set_transpose(12.0);
+    assert_frequency(tuning.get_frequency(69), 830.0);
+
+    tuning.set_transpose(0.0);
+    tuning.set_fine_tune(100.0);
+    assert_frequency(tuning.get_frequency(69), 415.0 * (2.0 as f32).powf(1.0 / 12.0));
+}
+
+#[test]
+fn transposing_retunes_held_notes(){
+    let events = vec![
+        TimedSynthEvent::new(0.0, SynthEvent::NotePress(57, 1.0)),
+        TimedSynthEvent::new(0.1, SynthEvent::ChangeTuning(TuningParam::Transpose, 12.0)),
+    ];
+    let left = render_events_left(events, 1.2, 0.2);
+
+    let octave_up = power_at(&left, 440.0);
+    let untransposed = power_at(&left, 220.0);
+    assert!(octave_up > untransposed * 100.0, "A held note should move up an octave, {} at 440Hz against {} at 220Hz", octave_up, untransposed);
+}
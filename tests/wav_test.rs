@@ -0,0 +1,142 @@
+use std::io::Cursor;
+
+use rodio::buffer::SamplesBuffer;
+
+use oxidizer::constants::*;
+use oxidizer::wav::{WavWriter, WavRecorder, WavFormat, parse_wav, read_wav};
+
+fn read_u16(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]])
+}
+
+fn write_to_buffer(samples: &[f32], format: WavFormat, dither: bool) -> Vec<u8> {
+    let mut buffer = Cursor::new(Vec::new());
+
+    let mut writer = WavWriter::new(&mut buffer, format, dither).unwrap();
+    writer.write_samples(samples).unwrap();
+    writer.finalize().unwrap();
+
+    return buffer.into_inner();
+}
+
+#[test]
+fn header_describes_the_written_data(){
+    let samples = [0.0, 0.5, -0.5, 1.0];
+
+    for format in [WavFormat::Int16, WavFormat::Int24, WavFormat::Float32] {
+        let bytes = write_to_buffer(&samples, format, false);
+        let bytes_per_sample = format.bits_per_sample() as usize / 8;
+
+        assert_eq!(&bytes[0..4], b"RIFF");
+        assert_eq!(&bytes[8..12], b"WAVE");
+        assert_eq!(read_u32(&bytes, 4) as usize, bytes.len() - 8, "RIFF size should be patched on finalize");
+        assert_eq!(read_u16(&bytes, 22), NUM_CHANNELS);
+        assert_eq!(read_u32(&bytes, 24), SAMPLE_RATE as u32);
+        assert_eq!(read_u16(&bytes, 34), format.bits_per_sample());
+        assert_eq!(&bytes[36..40], b"data");
+        assert_eq!(read_u32(&bytes, 40) as usize, samples.len() * bytes_per_sample, "data size should be patched on finalize");
+    }
+}
+
+#[test]
+fn samples_are_quantized_for_integer_formats(){
+    let bytes = write_to_buffer(&[0.5, -1.0, 2.0], WavFormat::Int16, false);
+
+    assert_eq!(i16::from_le_bytes([bytes[44], bytes[45]]), 16384);
+    assert_eq!(i16::from_le_bytes([bytes[46], bytes[47]]), -32767);
+    assert_eq!(i16::from_le_bytes([bytes[48], bytes[49]]), i16::MAX, "Out of range samples should be clipped");
+
+    let dithered = write_to_buffer(&[0.5; 64], WavFormat::Int16, true);
+    for i in 0..64 {
+        let value = i16::from_le_bytes([dithered[44 + i * 2], dithered[45 + i * 2]]);
+        assert!((value - 16384).abs() <= 1, "TPDF dither should stay within one LSB");
+    }
+}
+
+#[test]
+fn written_files_read_back(){
+    // Interleaved stereo frames, read back mixed down to mono
+    let samples = [0.0, 0.0, 0.5, 0.5, -0.5, 0.0, 0.25, -0.25];
+    let mono = [0.0, 0.5, -0.25, 0.0];
+
+    for format in [WavFormat::Int16, WavFormat::Int24, WavFormat::Float32] {
+        let wav = parse_wav(&write_to_buffer(&samples, format, false)).unwrap();
+
+        assert_eq!(wav.sample_rate, SAMPLE_RATE as u32);
+        assert_eq!(wav.samples.len(), samples.len() / NUM_CHANNELS as usize);
+        for (read, written) in wav.samples.iter().zip(mono.iter()) {
+            assert!((read - written).abs() < 1e-4, "{:?} read back {} instead of {}", format, read, written);
+        }
+    }
+
+    assert!(parse_wav(b"RIFF\0\0\0\0AVI ").is_err(), "Non WAVE files should be rejected");
+}
+
+#[test]
+fn recorder_passes_samples_through_and_finishes_the_file(){
+    // Several blocks and a partial one, as stereo frames with the same sample on both sides
+    let samples: Vec<f32> = (0..20000).map(|i| ((i / 2) % 100) as f32 / 100.0 - 0.5).collect();
+    let path = std::env::temp_dir().join(format!("oxidizer_recorder_test_{}.wav", std::process::id()));
+
+    let source = SamplesBuffer::new(NUM_CHANNELS, SAMPLE_RATE as u32, samples.clone());
+    let (recorder, recording) = WavRecorder::new(source, &path, WavFormat::Float32, false).unwrap();
+    let played: Vec<f32> = recorder.collect();
+    recording.finish().unwrap();
+
+    let wav = read_wav(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(played, samples, "Recorder should pass the samples through unchanged");
+    assert_eq!(wav.samples.len(), samples.len() / NUM_CHANNELS as usize, "Every frame should be in the file");
+    for (read, written) in wav.samples.iter().zip(samples.iter().step_by(NUM_CHANNELS as usize)) {
+        assert_eq!(read, written, "Recorded file should hold the played samples");
+    }
+}
+
+#[test]
+fn finishing_a_recording_keeps_the_recorders_last_block(){
+    // Ten seconds of stereo frames, far more than gets played
+    let samples: Vec<f32> = (0..SAMPLE_RATE as usize * 20).map(|i| ((i / 2) % 100) as f32 / 100.0 - 0.5).collect();
+    let path = std::env::temp_dir().join(format!("oxidizer_recorder_finish_test_{}.wav", std::process::id()));
+
+    let source = SamplesBuffer::new(NUM_CHANNELS, SAMPLE_RATE as u32, samples.clone());
+    let (mut recorder, recording) = WavRecorder::new(source, &path, WavFormat::Float32, false).unwrap();
+
+    // A block and a partial one, then finished while the audio thread is still playing
+    let played = recorder.by_ref().take(5000).count();
+    let finishing = std::thread::spawn(move || recording.finish());
+    while !finishing.is_finished() {
+        recorder.next();
+    }
+    finishing.join().unwrap().unwrap();
+
+    let wav = read_wav(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert!(wav.samples.len() * NUM_CHANNELS as usize >= played, "Every frame played before finishing should be in the file, {} of {}", wav.samples.len(), played / NUM_CHANNELS as usize);
+    for (read, written) in wav.samples.iter().zip(samples.iter().step_by(NUM_CHANNELS as usize)) {
+        assert_eq!(read, written, "Recorded file should hold the played samples");
+    }
+}
+
+#[test]
+fn finishing_a_recording_that_is_no_longer_played_keeps_its_whole_blocks(){
+    let samples: Vec<f32> = (0..10000).map(|i| ((i / 2) % 100) as f32 / 100.0 - 0.5).collect();
+    let path = std::env::temp_dir().join(format!("oxidizer_recorder_stopped_test_{}.wav", std::process::id()));
+
+    let source = SamplesBuffer::new(NUM_CHANNELS, SAMPLE_RATE as u32, samples);
+    let (mut recorder, recording) = WavRecorder::new(source, &path, WavFormat::Float32, false).unwrap();
+    assert_eq!(recorder.by_ref().take(5000).count(), 5000);
+
+    // The recorder is still alive but never plays again
+    recording.finish().unwrap();
+    let wav = read_wav(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    drop(recorder);
+
+    assert_eq!(wav.samples.len(), 2048, "Only the block handed over before finishing should be in the file");
+}